@@ -1,11 +1,11 @@
 command -v rustc || curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh
 source ~/.profile
 command -v wasm-pack || curl https://rustwasm.github.io/wasm-pack/installer/init.sh -sSf | sh
-if [ -e data-bundler/data/words-index.json.gz ]
+if [ -e data-bundler/data/index.bin.gz ]
 then
     echo "found indices, skipping index creation"
 else
-  cargo run --release --bin data-bundler
+  cargo run --release --bin data-bundler -- all --install
 fi
 wasm-pack build --release client/
 pushd client/web/
@@ -1,27 +1,30 @@
-extern crate phf;
 extern crate rust_stemmers;
 extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
-use primitive_types::U256;
 use regex::Regex;
 use rust_stemmers::{Algorithm, Stemmer};
+use serde::Serialize;
 
 extern crate scripture_types;
-extern crate data_bundler;
 
 mod utils;
 mod preferences;
+mod query;
+mod ranking;
+mod reference;
+mod terms;
 
 #[macro_use]
 extern crate lazy_static;
 
 use scripture_types::{
     BookOfMormon, DoctrineAndCovenants, NewTestament, OldTestament, VersePathsIndex, PearlOfGreatPrice,
-    VersePath
+    VersePath, HeadingPath, HeadingField, FrontMatterPath,
 };
 use fnv::FnvHashMap;
-use fnv::FnvHashSet;
+use std::sync::RwLock;
+use indices::artifact::{self, ArtifactError};
 use wasm_bindgen::prelude::*;
 
 extern crate web_sys;
@@ -39,59 +42,71 @@ macro_rules! log {
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-static BIN_OLD_TESTAMENT: &'static [u8] =
-    include_bytes!("../../data-bundler/data/old-testament.json.bin");
-static BIN_NEW_TESTAMENT: &'static [u8] =
-    include_bytes!("../../data-bundler/data/new-testament.json.bin");
-static BIN_BOOK_OF_MORMON: &'static [u8] =
-    include_bytes!("../../data-bundler/data/book-of-mormon.json.bin");
-static BIN_DOCTRINE_AND_COVENANTS: &'static [u8] =
-    include_bytes!("../../data-bundler/data/doctrine-and-covenants.json.bin");
-static BIN_PEARL_OF_GREAT_PRICE: &'static [u8] =
-    include_bytes!("../../data-bundler/data/pearl-of-great-price.json.bin");
-
 static BASE_URL: &'static str = "https://www.churchofjesuschrist.org/study/scriptures";
+// The Three Witnesses' testimony comes first in the data, then the Eight's.
+static TESTIMONY_SLUGS: [&str; 2] = ["three", "eight"];
+
+// Orders hits that belong to the same verse: front matter that leads into a
+// book, then headings in `HeadingField` order, the verse itself, and anything
+// closing a section.
+const LEADING_RANK: u8 = 0;
+const VERSE_RANK: u8 = 100;
+const TRAILING_RANK: u8 = 101;
+
+// Each collection is fetched on its own, so searching can start before the
+// whole canon has downloaded. Filled in by `load_collection`.
+#[derive(Default)]
+struct Corpora {
+    ot: Option<&'static OldTestament>,
+    nt: Option<&'static NewTestament>,
+    bom: Option<&'static BookOfMormon>,
+    dc: Option<&'static DoctrineAndCovenants>,
+    pogp: Option<&'static PearlOfGreatPrice>,
+    // The corpus version of each loaded collection, to check against the
+    // index's when it arrives after them.
+    versions: Vec<String>,
+}
+
 lazy_static! {
-    static ref BOOK_OF_MORMON: BookOfMormon = adserde(BIN_BOOK_OF_MORMON);
-    static ref OLD_TESTAMENT: OldTestament = adserde(BIN_OLD_TESTAMENT);
-    static ref NEW_TESTAMENT: NewTestament = adserde(BIN_NEW_TESTAMENT);
-    static ref DOCTRINE_AND_COVENANTS: DoctrineAndCovenants =
-        adserde(BIN_DOCTRINE_AND_COVENANTS);
-    static ref PEARL_OF_GREAT_PRICE: PearlOfGreatPrice = adserde(BIN_PEARL_OF_GREAT_PRICE);
+    static ref CORPORA: RwLock<Corpora> = RwLock::new(Corpora::default());
 
-    static ref VERSE_PATHS_INDEX: VersePathsIndex = scripture_types::paths_to_verse_paths_index(&indices::PHF_PATHS_INDEX);
+    // Set by `load_index`, before anything is searched.
+    static ref INDEX: RwLock<Option<&'static indices::Index<'static>>> = RwLock::new(None);
+    static ref INDEX_CORPUS: RwLock<Option<String>> = RwLock::new(None);
+    static ref VERSE_PATHS_INDEX: VersePathsIndex = scripture_types::paths_to_verse_paths_index(&index().metadata.paths);
 
     static ref STEMMER: rust_stemmers::Stemmer = Stemmer::create(Algorithm::English);
-    static ref RE_VERSE_CHARS: Regex = Regex::new(r"[^A-Za-z0-9\sæ\-]").unwrap();
+    // `*` is kept for wildcard searches.
+    static ref RE_VERSE_CHARS: Regex = Regex::new(r"[^A-Za-z0-9\sæ\-\*]").unwrap();
 }
 
 fn make_link(verse_path: &scripture_types::VersePath) -> String {
     let url_slug = match verse_path {
         VersePath::PathOT(b, c, v) => {
-            let coll = &(&*OLD_TESTAMENT);
+            let coll = old_testament();
 
             let book = &coll.books[*b as usize];
             format!("{}/{}/{}.{}", coll.lds_slug, book.lds_slug, c + 1, v + 1)
         }
         VersePath::PathNT(b, c, v) => {
-            let coll = &(&*NEW_TESTAMENT);
+            let coll = new_testament();
 
             let book = &coll.books[*b as usize];
             format!("{}/{}/{}.{}", coll.lds_slug, book.lds_slug, c + 1, v + 1)
         }
         VersePath::PathBoM(b, c, v) => {
-            let coll = &(&*BOOK_OF_MORMON);
+            let coll = book_of_mormon();
 
             let book = &coll.books[*b as usize];
             format!("{}/{}/{}.{}", coll.lds_slug, book.lds_slug, c + 1, v + 1)
         }
         VersePath::PathDC(s, v) => {
-            let coll = &(&*DOCTRINE_AND_COVENANTS);
+            let coll = doctrine_and_covenants();
 
             format!("{}/{}.{}", coll.lds_slug, s + 1, v + 1)
         }
         VersePath::PathPOGP(b, c, v) => {
-            let coll = &(&*PEARL_OF_GREAT_PRICE);
+            let coll = pearl_of_great_price();
 
             let book = &coll.books[*b as usize];
             format!(
@@ -106,17 +121,19 @@ fn make_link(verse_path: &scripture_types::VersePath) -> String {
     format!("{}/{}", BASE_URL, url_slug)
 }
 
-fn extract_highlights((start_indices, lengths): &(U256, u128)) -> Vec<(u16, u8)> {
-    data_bundler::unpack_indices(*start_indices).iter().cloned().zip(data_bundler::unpack_lengths(*lengths)).collect()
+// A posting that can't be decoded still counts as a match, just without
+// anything to highlight.
+fn extract_highlights(posting: &[u8]) -> Vec<query::Highlight> {
+    indices::decode_highlights(posting).unwrap_or_default()
 }
 
-fn highlight_matches(text: &String, highlights: &Vec<(u16, u8)>) -> String {
+fn highlight_matches(text: &String, highlights: &[query::Highlight]) -> String {
     highlights
         .iter()
         .rev()
         .fold(text.to_string(), |mut acc, (from, to)| {
-            let from_usize = *from as usize;
-            let to_usize = from_usize + (*to as usize);
+            let from_usize = *from;
+            let to_usize = from_usize + *to;
             // can't just use the slice because that
             // runs afoul of intended borrow checker usage.
             let word_to_replace = String::from(&acc[from_usize..to_usize]);
@@ -132,19 +149,19 @@ fn print_chapter(
     p: &scripture_types::VersePath,
 ) -> String {
     let verses = match p {
-        VersePath::PathOT(b, c, _) => &(&*OLD_TESTAMENT).books[*b as usize].chapters[*c as usize].verses,
-        VersePath::PathNT(b, c, _) => &(&*NEW_TESTAMENT).books[*b as usize].chapters[*c as usize].verses,
-        VersePath::PathBoM(b, c, _) => &(&*BOOK_OF_MORMON).books[*b as usize].chapters[*c as usize].verses,
-        VersePath::PathDC(s, _) => &(&*DOCTRINE_AND_COVENANTS).sections[*s as usize].verses,
-        VersePath::PathPOGP(b, c, _) => &(&*PEARL_OF_GREAT_PRICE).books[*b as usize].chapters[*c as usize].verses,
+        VersePath::PathOT(b, c, _) => &old_testament().books[*b as usize].chapters[*c as usize].verses,
+        VersePath::PathNT(b, c, _) => &new_testament().books[*b as usize].chapters[*c as usize].verses,
+        VersePath::PathBoM(b, c, _) => &book_of_mormon().books[*b as usize].chapters[*c as usize].verses,
+        VersePath::PathDC(s, _) => &doctrine_and_covenants().sections[*s as usize].verses,
+        VersePath::PathPOGP(b, c, _) => &pearl_of_great_price().books[*b as usize].chapters[*c as usize].verses,
     };
 
     let title = match p {
-        VersePath::PathOT(b, c, _) => &(&*OLD_TESTAMENT).books[*b as usize].chapters[*c as usize].reference,
-        VersePath::PathNT(b, c, _) => &(&*NEW_TESTAMENT).books[*b as usize].chapters[*c as usize].reference,
-        VersePath::PathBoM(b, c, _) => &(&*BOOK_OF_MORMON).books[*b as usize].chapters[*c as usize].reference,
-        VersePath::PathDC(s, _) => &(&*DOCTRINE_AND_COVENANTS).sections[*s as usize].reference,
-        VersePath::PathPOGP(b, c, _) => &(&*PEARL_OF_GREAT_PRICE).books[*b as usize].chapters[*c as usize].reference,
+        VersePath::PathOT(b, c, _) => &old_testament().books[*b as usize].chapters[*c as usize].reference,
+        VersePath::PathNT(b, c, _) => &new_testament().books[*b as usize].chapters[*c as usize].reference,
+        VersePath::PathBoM(b, c, _) => &book_of_mormon().books[*b as usize].chapters[*c as usize].reference,
+        VersePath::PathDC(s, _) => &doctrine_and_covenants().sections[*s as usize].reference,
+        VersePath::PathPOGP(b, c, _) => &pearl_of_great_price().books[*b as usize].chapters[*c as usize].reference,
         
     };
 
@@ -157,8 +174,51 @@ fn print_chapter(
     format!("<h3 class=\"title\">{}</h3><ul>{}</ul>", title, inner)
 }
 
+fn print_front_matter(p: &FrontMatterPath) -> Option<String> {
+    let numbered = |paragraphs: &[String]| -> String {
+        paragraphs
+            .iter()
+            .enumerate()
+            .map(|(i, paragraph)| format!("<li><strong>{}</strong>{}</li>", i + 1, paragraph))
+            .collect::<Vec<String>>()
+            .join("")
+    };
+
+    let preview = match p {
+        FrontMatterPath::BoMTitlePage(_) => {
+            let title_page = &book_of_mormon().title_page;
+            format!("<h3 class=\"title\">{}</h3><ul>{}</ul>", title_page.title, numbered(&title_page.text))
+        },
+        FrontMatterPath::BoMTestimony(t) => {
+            let testimony = book_of_mormon().testimonies.get(*t as usize)?;
+            format!(
+                "<h3 class=\"title\">{}</h3><p>{}</p><ul>{}</ul>",
+                testimony.title,
+                testimony.text,
+                testimony.witnesses.iter().map(|witness| format!("<li>{}</li>", witness)).collect::<Vec<String>>().join(""),
+            )
+        },
+        FrontMatterPath::NTTitlePage => {
+            let title_page = &new_testament().title_page;
+            format!("<h3 class=\"title\">{}</h3><p>{}</p>", title_page.title, title_page.text)
+        },
+        FrontMatterPath::FacsimileExplanation(b, f, _) => {
+            let facsimile = facsimile(*b, *f)?;
+            format!("<h3 class=\"title\">{}</h3><ul>{}</ul>", facsimile.title, numbered(&facsimile.explanations))
+        },
+        FrontMatterPath::DCSignature(s) => {
+            let signature = doctrine_and_covenants().sections.get(*s as usize)?.signature.as_ref()?;
+            format!("{}<p>{}</p>", print_chapter(&VersePath::PathDC(*s, 0)), signature)
+        },
+    };
+    Some(preview)
+}
+
 #[wasm_bindgen]
 pub fn get_chapter_preview(preview_str: String) -> String {
+    if let Ok(p) = serde_json::from_str::<FrontMatterPath>(&preview_str) {
+        return print_front_matter(&p).unwrap_or_default();
+    }
     match serde_json::from_str(&preview_str) {
         Ok(p) => {
             let chapter: String = print_chapter(&p);
@@ -174,10 +234,9 @@ pub fn get_chapter_preview(preview_str: String) -> String {
 fn format_verse(
     p: &scripture_types::VersePath,
     v: &scripture_types::Verse,
-    highlights: &Vec<(u16, u8)>,
+    highlights: &[query::Highlight],
 ) -> String {
-    let mut sorted_highlights = highlights.clone();
-    sorted_highlights.sort(); 
+    let sorted_highlights = query::merge_highlights(highlights);
     format!(
         "<li data-verse-path={}><a target=\"_blank\" rel=\"noopener noreferrer\" href=\"{}\">{}</a>: {}</li>",
         serde_json::to_string(p).unwrap(),
@@ -187,15 +246,277 @@ fn format_verse(
     )
 }
 
-pub fn adserde<T: serde::de::DeserializeOwned + serde::ser::Serialize>(s: &'static [u8]) -> T {
+fn field_label(field: HeadingField) -> &'static str {
+    match field {
+        HeadingField::BookHeading => "Book heading",
+        HeadingField::BookNote => "Book note",
+        HeadingField::ChapterHeading => "Chapter heading",
+        HeadingField::ChapterNote => "Chapter note",
+        HeadingField::VerseHeading => "Verse heading",
+        HeadingField::VerseSubheading => "Verse subheading",
+    }
+}
+
+fn format_heading(
+    p: &HeadingPath,
+    preferences: &preferences::SearchPreferences,
+    highlights: &[query::Highlight],
+) -> String {
+    let (reference, text) = resolve_heading_path(p, preferences);
+    let sorted_highlights = query::merge_highlights(highlights);
+    format!(
+        "<li data-verse-path={} data-field={}><a target=\"_blank\" rel=\"noopener noreferrer\" href=\"{}\">{}</a> <span class=\"field\">{}</span>: {}</li>",
+        serde_json::to_string(&p.verse_path).unwrap(),
+        serde_json::to_string(&p.field).unwrap(),
+        make_link(&p.verse_path),
+        reference,
+        field_label(p.field),
+        highlight_matches(text, &sorted_highlights),
+    )
+}
+
+fn facsimile(book: u8, facsimile: u8) -> Option<&'static scripture_types::Facsimile> {
+    pearl_of_great_price().books.get(book as usize)?.facsimiles.as_ref()?.get(facsimile as usize)
+}
+
+// The reference, label and text of a front matter passage, or None if the
+// path doesn't point at anything in the loaded scriptures.
+pub fn resolve_front_matter_path(p: &FrontMatterPath) -> Option<(String, String, &'static String)> {
+    let resolved = match p {
+        FrontMatterPath::BoMTitlePage(paragraph) => {
+            let title_page = &book_of_mormon().title_page;
+            (title_page.title.to_string(), String::from("Title page"), title_page.text.get(*paragraph as usize)?)
+        },
+        FrontMatterPath::BoMTestimony(t) => {
+            let testimony = book_of_mormon().testimonies.get(*t as usize)?;
+            (testimony.title.to_string(), String::from("Testimony"), &testimony.text)
+        },
+        FrontMatterPath::NTTitlePage => {
+            let title_page = &new_testament().title_page;
+            (title_page.title.to_string(), String::from("Title page"), &title_page.text)
+        },
+        FrontMatterPath::FacsimileExplanation(b, f, e) => {
+            let facsimile = facsimile(*b, *f)?;
+            (
+                facsimile.title.to_string(),
+                format!("Explanation {}", e + 1),
+                facsimile.explanations.get(*e as usize)?,
+            )
+        },
+        FrontMatterPath::DCSignature(s) => {
+            let section = doctrine_and_covenants().sections.get(*s as usize)?;
+            (section.reference.to_string(), String::from("Signature"), section.signature.as_ref()?)
+        },
+    };
+    Some(resolved)
+}
+
+// The verse a front matter passage is sorted and filtered alongside, and
+// whether it comes before or after that verse.
+fn front_matter_anchor(p: &FrontMatterPath) -> (VersePath, u8) {
+    match p {
+        FrontMatterPath::BoMTitlePage(_) | FrontMatterPath::BoMTestimony(_) => (VersePath::PathBoM(0, 0, 0), LEADING_RANK),
+        FrontMatterPath::NTTitlePage => (VersePath::PathNT(0, 0, 0), LEADING_RANK),
+        FrontMatterPath::FacsimileExplanation(b, _, _) => (VersePath::PathPOGP(*b, 0, 0), LEADING_RANK),
+        FrontMatterPath::DCSignature(s) => {
+            let last_verse = doctrine_and_covenants()
+                .sections
+                .get(*s as usize)
+                .map(|section| section.verses.len().saturating_sub(1))
+                .unwrap_or(0);
+            (VersePath::PathDC(*s, last_verse as u16), TRAILING_RANK)
+        },
+    }
+}
+
+fn make_front_matter_link(p: &FrontMatterPath) -> Option<String> {
+    let url_slug = match p {
+        FrontMatterPath::BoMTitlePage(_) => format!("{}/bofm-title?lang=eng", book_of_mormon().lds_slug),
+        FrontMatterPath::BoMTestimony(t) => format!(
+            "{}/{}?lang=eng",
+            book_of_mormon().lds_slug,
+            TESTIMONY_SLUGS.get(*t as usize).unwrap_or(&"introduction"),
+        ),
+        FrontMatterPath::NTTitlePage => format!("{}/title-page?lang=eng", new_testament().lds_slug),
+        FrontMatterPath::FacsimileExplanation(b, f, _) => {
+            let coll = pearl_of_great_price();
+            let book = coll.books.get(*b as usize)?;
+            let facsimile = facsimile(*b, *f)?;
+            format!("{}/{}/{}?lang=eng", coll.lds_slug, book.lds_slug, facsimile.lds_slug)
+        },
+        FrontMatterPath::DCSignature(_) => return Some(make_link(&front_matter_anchor(p).0)),
+    };
+    Some(format!("{}/{}", BASE_URL, url_slug))
+}
+
+// Unit variants serialize to a bare JSON string, whose quotes an unquoted
+// attribute would swallow, so they're written in their object form instead.
+fn front_matter_attribute(p: &FrontMatterPath) -> Option<String> {
+    match serde_json::to_value(p).ok()? {
+        serde_json::Value::String(variant) => Some(format!("{{\"{}\":null}}", variant)),
+        value => Some(value.to_string()),
+    }
+}
+
+fn format_front_matter(p: &FrontMatterPath, highlights: &[query::Highlight]) -> Option<String> {
+    let (reference, label, text) = resolve_front_matter_path(p)?;
+    let sorted_highlights = query::merge_highlights(highlights);
+    Some(format!(
+        "<li data-verse-path={}><a target=\"_blank\" rel=\"noopener noreferrer\" href=\"{}\">{}</a> <span class=\"field\">{}</span>: {}</li>",
+        front_matter_attribute(p)?,
+        make_front_matter_link(p)?,
+        reference,
+        label,
+        highlight_matches(text, &sorted_highlights),
+    ))
+}
+
+pub fn adserde<T: serde::de::DeserializeOwned>(s: &[u8]) -> Result<T, bincode::Error> {
     let t_0 = web_sys::window().unwrap().performance().unwrap().now();
 
-    let data: T = bincode::deserialize(s).unwrap();
+    let data = bincode::deserialize(s);
     let t_1 = web_sys::window().unwrap().performance().unwrap().now();
     log!("DATA LOAD: {:?}", t_1 - t_0);
     data
 }
 
+fn index() -> &'static indices::Index<'static> {
+    INDEX.read().unwrap().expect("load_index has to be called before searching")
+}
+
+// Only call these for collections that `is_loaded`.
+fn old_testament() -> &'static OldTestament {
+    CORPORA.read().unwrap().ot.expect("the Old Testament isn't loaded")
+}
+
+fn new_testament() -> &'static NewTestament {
+    CORPORA.read().unwrap().nt.expect("the New Testament isn't loaded")
+}
+
+fn book_of_mormon() -> &'static BookOfMormon {
+    CORPORA.read().unwrap().bom.expect("the Book of Mormon isn't loaded")
+}
+
+fn doctrine_and_covenants() -> &'static DoctrineAndCovenants {
+    CORPORA.read().unwrap().dc.expect("the Doctrine and Covenants isn't loaded")
+}
+
+fn pearl_of_great_price() -> &'static PearlOfGreatPrice {
+    CORPORA.read().unwrap().pogp.expect("the Pearl of Great Price isn't loaded")
+}
+
+// In canonical order.
+const COLLECTIONS: [reference::Collection; 5] = [
+    reference::Collection::OT,
+    reference::Collection::NT,
+    reference::Collection::BoM,
+    reference::Collection::DC,
+    reference::Collection::PoGP,
+];
+
+fn is_loaded(collection: reference::Collection) -> bool {
+    let corpora = CORPORA.read().unwrap();
+    match collection {
+        reference::Collection::OT => corpora.ot.is_some(),
+        reference::Collection::NT => corpora.nt.is_some(),
+        reference::Collection::BoM => corpora.bom.is_some(),
+        reference::Collection::DC => corpora.dc.is_some(),
+        reference::Collection::PoGP => corpora.pogp.is_some(),
+    }
+}
+
+// Collections are named the same way as in `IncludedSources`.
+fn collection_key(collection: reference::Collection) -> &'static str {
+    match collection {
+        reference::Collection::OT => "ot",
+        reference::Collection::NT => "nt",
+        reference::Collection::BoM => "bom",
+        reference::Collection::DC => "dc",
+        reference::Collection::PoGP => "pogp",
+    }
+}
+
+fn source_included(collection: reference::Collection, preferences: &preferences::SearchPreferences) -> bool {
+    let sources = &preferences.included_sources;
+    match collection {
+        reference::Collection::OT => sources.ot,
+        reference::Collection::NT => sources.nt,
+        reference::Collection::BoM => sources.bom,
+        reference::Collection::DC => sources.dc,
+        reference::Collection::PoGP => sources.pogp,
+    }
+}
+
+fn verse_path_collection(verse_path: &VersePath) -> reference::Collection {
+    match verse_path {
+        VersePath::PathOT(_, _, _) => reference::Collection::OT,
+        VersePath::PathNT(_, _, _) => reference::Collection::NT,
+        VersePath::PathBoM(_, _, _) => reference::Collection::BoM,
+        VersePath::PathDC(_, _) => reference::Collection::DC,
+        VersePath::PathPOGP(_, _, _) => reference::Collection::PoGP,
+    }
+}
+
+fn front_matter_collection(p: &FrontMatterPath) -> reference::Collection {
+    match p {
+        FrontMatterPath::BoMTitlePage(_) | FrontMatterPath::BoMTestimony(_) => reference::Collection::BoM,
+        FrontMatterPath::NTTitlePage => reference::Collection::NT,
+        FrontMatterPath::FacsimileExplanation(_, _, _) => reference::Collection::PoGP,
+        FrontMatterPath::DCSignature(_) => reference::Collection::DC,
+    }
+}
+
+// Takes one collection's `.json.bin`, gzipped or not, named as in
+// `IncludedSources`. Until a collection is loaded, searches report its hits
+// as pending. Fails if the file's header doesn't match this client or the
+// index.
+#[wasm_bindgen]
+pub fn load_collection(key: &str, bytes: Vec<u8>) -> Result<(), JsValue> {
+    let bytes = indices::compression::decompress(bytes)
+        .map_err(|error| JsValue::from_str(&format!("can't decompress {}: {}", key, error)))?;
+    let invalid = |error: ArtifactError| JsValue::from_str(&format!("can't load {}: {}", key, error));
+    let (corpus_version, bytes) = artifact::unpack(&bytes).map_err(invalid)?;
+    if let Some(index_corpus) = &*INDEX_CORPUS.read().unwrap() {
+        artifact::check_collection(&corpus_version, index_corpus).map_err(invalid)?;
+    }
+
+    let unreadable = |error: bincode::Error| JsValue::from_str(&format!("can't read {}: {}", key, error));
+    let mut corpora = CORPORA.write().unwrap();
+    match key {
+        "ot" => corpora.ot = Some(Box::leak(Box::new(adserde(bytes).map_err(unreadable)?))),
+        "nt" => corpora.nt = Some(Box::leak(Box::new(adserde(bytes).map_err(unreadable)?))),
+        "bom" => corpora.bom = Some(Box::leak(Box::new(adserde(bytes).map_err(unreadable)?))),
+        "dc" => corpora.dc = Some(Box::leak(Box::new(adserde(bytes).map_err(unreadable)?))),
+        "pogp" => corpora.pogp = Some(Box::leak(Box::new(adserde(bytes).map_err(unreadable)?))),
+        _ => return Err(JsValue::from_str(&format!("unknown collection {:?}", key))),
+    }
+    corpora.versions.push(corpus_version);
+    Ok(())
+}
+
+// Takes the `index.bin` written by data-bundler, gzipped or not. The index is
+// kept for as long as the page is open, so its bytes are never freed. Fails
+// if the file's header doesn't match this client or the loaded collections.
+#[wasm_bindgen]
+pub fn load_index(bytes: Vec<u8>) -> Result<(), JsValue> {
+    let t_0 = web_sys::window().unwrap().performance().unwrap().now();
+    let bytes = indices::compression::decompress(bytes)
+        .map_err(|error| JsValue::from_str(&format!("can't decompress index: {}", error)))?;
+    let bytes: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+    let invalid = |error: ArtifactError| JsValue::from_str(&format!("can't load index: {}", error));
+    let (corpus_version, bytes) = artifact::unpack(bytes).map_err(invalid)?;
+    for collection_corpus in &CORPORA.read().unwrap().versions {
+        artifact::check_collection(collection_corpus, &corpus_version).map_err(invalid)?;
+    }
+
+    let index = indices::Index::from_bytes(bytes).map_err(|error| JsValue::from_str(&error.to_string()))?;
+    *INDEX.write().unwrap() = Some(Box::leak(Box::new(index)));
+    *INDEX_CORPUS.write().unwrap() = Some(corpus_version);
+    let t_1 = web_sys::window().unwrap().performance().unwrap().now();
+    log!("INDEX LOAD: {:?}", t_1 - t_0);
+    Ok(())
+}
+
 #[wasm_bindgen]
 pub fn bootstrap_searcher() {
     let t_0 = web_sys::window().unwrap().performance().unwrap().now();
@@ -206,8 +527,8 @@ pub fn bootstrap_searcher() {
 
     log!(
         "words: {:?}, paths: {:?}, verse_paths: {:?}",
-        (&indices::PHF_WORDS_INDEX).len(),
-        (&indices::PHF_PATHS_INDEX).len(),
+        index().stem_count(),
+        index().metadata.paths.len(),
         num_verse_paths,
     );
 
@@ -226,8 +547,7 @@ fn make_splittable(text: &String) -> String {
         .replace("–", " ")
         .replace("—", " ")
         .replace("—", " ")
-        .replace("'s", "")
-        .to_lowercase();
+        .replace("'s", "");
     let splittable = RE_VERSE_CHARS.replace_all(&with_substitutions, "");
     splittable.to_string()
 }
@@ -237,122 +557,640 @@ pub fn resolve_verse_path(
     _preferences: &preferences::SearchPreferences,
 ) -> &'static scripture_types::Verse {
     match path {
-        VersePath::PathOT(b, c, v) => &(&*OLD_TESTAMENT).books[*b as usize].chapters[*c as usize].verses[*v as usize],
-        VersePath::PathNT(b, c, v) => &(&*NEW_TESTAMENT).books[*b as usize].chapters[*c as usize].verses[*v as usize],
-        VersePath::PathBoM(b, c, v) => &(&*BOOK_OF_MORMON).books[*b as usize].chapters[*c as usize].verses[*v as usize],
-        VersePath::PathDC(s, v) => &(&*DOCTRINE_AND_COVENANTS).sections[*s as usize].verses[*v as usize],
-        VersePath::PathPOGP(b, c, v) => &(&*PEARL_OF_GREAT_PRICE).books[*b as usize].chapters[*c as usize].verses[*v as usize],
+        VersePath::PathOT(b, c, v) => &old_testament().books[*b as usize].chapters[*c as usize].verses[*v as usize],
+        VersePath::PathNT(b, c, v) => &new_testament().books[*b as usize].chapters[*c as usize].verses[*v as usize],
+        VersePath::PathBoM(b, c, v) => &book_of_mormon().books[*b as usize].chapters[*c as usize].verses[*v as usize],
+        VersePath::PathDC(s, v) => &doctrine_and_covenants().sections[*s as usize].verses[*v as usize],
+        VersePath::PathPOGP(b, c, v) => &pearl_of_great_price().books[*b as usize].chapters[*c as usize].verses[*v as usize],
+    }
+}
+
+fn resolve_book(path: &VersePath) -> Option<&'static scripture_types::Book> {
+    match path {
+        VersePath::PathOT(b, _, _) => Some(&old_testament().books[*b as usize]),
+        VersePath::PathNT(b, _, _) => Some(&new_testament().books[*b as usize]),
+        VersePath::PathBoM(b, _, _) => Some(&book_of_mormon().books[*b as usize]),
+        VersePath::PathDC(_, _) => None,
+        VersePath::PathPOGP(b, _, _) => Some(&pearl_of_great_price().books[*b as usize]),
+    }
+}
+
+fn resolve_chapter(path: &VersePath) -> Option<&'static scripture_types::Chapter> {
+    match path {
+        VersePath::PathOT(_, c, _)
+        | VersePath::PathNT(_, c, _)
+        | VersePath::PathBoM(_, c, _)
+        | VersePath::PathPOGP(_, c, _) => resolve_book(path).map(|book| &book.chapters[*c as usize]),
+        VersePath::PathDC(_, _) => None,
+    }
+}
+
+// The reference and text of a heading or note.
+pub fn resolve_heading_path(
+    path: &HeadingPath,
+    preferences: &preferences::SearchPreferences,
+) -> (&'static String, &'static String) {
+    let (reference, text) = match path.field {
+        HeadingField::BookHeading | HeadingField::BookNote => {
+            let book = resolve_book(&path.verse_path).unwrap();
+            let text = if path.field == HeadingField::BookHeading { &book.heading } else { &book.note };
+            (&book.book, text)
+        },
+        HeadingField::ChapterHeading | HeadingField::ChapterNote => {
+            let chapter = resolve_chapter(&path.verse_path).unwrap();
+            let text = if path.field == HeadingField::ChapterHeading { &chapter.heading } else { &chapter.note };
+            (&chapter.reference, text)
+        },
+        HeadingField::VerseHeading | HeadingField::VerseSubheading => {
+            let verse = resolve_verse_path(&path.verse_path, preferences);
+            let text = if path.field == HeadingField::VerseHeading { &verse.heading } else { &verse.subheading };
+            (&verse.reference, text)
+        },
+    };
+    (reference, text.as_ref().unwrap())
+}
+
+fn passage_collection(scripture_id: &u16) -> Option<reference::Collection> {
+    let metadata = &index().metadata;
+    if let Some(verse_path) = metadata.paths.get(scripture_id) {
+        return Some(verse_path_collection(verse_path));
+    }
+    if let Some(heading_path) = metadata.heading_paths.get(scripture_id) {
+        return Some(verse_path_collection(&heading_path.verse_path));
+    }
+    metadata.front_matter_paths.get(scripture_id).map(front_matter_collection)
+}
+
+// Whether a passage's text is at hand to check phrases and NEAR against.
+fn passage_loaded(scripture_id: &u16) -> bool {
+    passage_collection(scripture_id).map(is_loaded).unwrap_or(false)
+}
+
+// The text a scripture id was indexed from: a verse, heading or front matter.
+// `None` until its collection is loaded.
+fn passage_text(scripture_id: &u16, preferences: &preferences::SearchPreferences) -> Option<&'static String> {
+    if !passage_loaded(scripture_id) {
+        return None;
+    }
+    if let Some(verse_path) = index().metadata.paths.get(scripture_id) {
+        return Some(&resolve_verse_path(verse_path, preferences).text);
     }
+    if let Some(heading_path) = index().metadata.heading_paths.get(scripture_id) {
+        return Some(resolve_heading_path(heading_path, preferences).1);
+    }
+    let front_matter_path = index().metadata.front_matter_paths.get(scripture_id)?;
+    resolve_front_matter_path(front_matter_path).map(|(_, _, text)| text)
 }
 
 fn check_collection_searchable(verse_path: &VersePath, preferences: &preferences::SearchPreferences) -> bool {
-    let return_value = match verse_path {
-        VersePath::PathOT(book, _, _) => (
+    match verse_path {
+        VersePath::PathOT(book, _, _) => {
             preferences.included_sources.ot &&
-            preferences.included_books.ot.contains(&(&*OLD_TESTAMENT).books[*book as usize].book)
-        ),
-        VersePath::PathNT(book, _, _) => (
+            preferences.included_books.ot.contains(&old_testament().books[*book as usize].book)
+        },
+        VersePath::PathNT(book, _, _) => {
             preferences.included_sources.nt &&
-            preferences.included_books.nt.contains(&(&*NEW_TESTAMENT).books[*book as usize].book)
-        ),
-        VersePath::PathBoM(book, _, _) => (
+            preferences.included_books.nt.contains(&new_testament().books[*book as usize].book)
+        },
+        VersePath::PathBoM(book, _, _) => {
             preferences.included_sources.bom &&
-            preferences.included_books.bom.contains(&(&*BOOK_OF_MORMON).books[*book as usize].book)
-        ),
-        VersePath::PathDC(sec, _) => (
+            preferences.included_books.bom.contains(&book_of_mormon().books[*book as usize].book)
+        },
+        VersePath::PathDC(sec, _) => {
             preferences.included_sources.dc &&
-            sec >= &(preferences.included_books.dc.0) &&
-            sec <= &(preferences.included_books.dc.1)
-        ),
+            *sec >= preferences.included_books.dc.0 &&
+            *sec <= preferences.included_books.dc.1
+        },
         VersePath::PathPOGP(book, _, _) => {
-            let title = &(&*PEARL_OF_GREAT_PRICE).books[*book as usize].book;
-            (
-                preferences.included_sources.pogp &&
-                preferences.included_books.pogp.contains(title)
-            )
+            let title = &pearl_of_great_price().books[*book as usize].book;
+            preferences.included_sources.pogp && preferences.included_books.pogp.contains(title)
+        },
+    }
+}
+
+// Title pages and testimonies go with their whole collection; facsimiles and
+// signatures with their book or section.
+fn check_front_matter_searchable(p: &FrontMatterPath, preferences: &preferences::SearchPreferences) -> bool {
+    match p {
+        FrontMatterPath::BoMTitlePage(_) | FrontMatterPath::BoMTestimony(_) => preferences.included_sources.bom,
+        FrontMatterPath::NTTitlePage => preferences.included_sources.nt,
+        FrontMatterPath::FacsimileExplanation(_, _, _) | FrontMatterPath::DCSignature(_) => {
+            check_collection_searchable(&front_matter_anchor(p).0, preferences)
+        },
+    }
+}
+
+// Verses of the referenced chapters, as (chapter index, verse index, verse).
+// `chapters` pairs each chapter's number with its verses.
+fn verses_in_reference<'a>(
+    chapters: Vec<(u16, &'a Vec<scripture_types::Verse>)>,
+    reference: &reference::Reference,
+) -> Vec<(u8, u16, &'a scripture_types::Verse)> {
+    chapters
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (chapter, _))| *chapter >= reference.from_chapter && *chapter <= reference.to_chapter)
+        .flat_map(|(chapter_index, (chapter, verses))| {
+            let from = if chapter == reference.from_chapter { reference.from_verse.unwrap_or(1) } else { 1 };
+            let to = if chapter == reference.to_chapter { reference.to_verse.unwrap_or(u16::MAX) } else { u16::MAX };
+            verses
+                .iter()
+                .enumerate()
+                .filter(move |(_, verse)| verse.verse >= from && verse.verse <= to)
+                .map(move |(verse_index, verse)| (chapter_index as u8, verse_index as u16, verse))
+        })
+        .collect()
+}
+
+fn book_reference_verses(
+    books: &'static [scripture_types::Book],
+    reference: &reference::Reference,
+    make_path: fn(u8, u8, u16) -> VersePath,
+) -> Vec<(VersePath, &'static scripture_types::Verse)> {
+    match books.iter().position(|book| book.book == reference.book) {
+        Some(book_index) => {
+            let chapters = books[book_index]
+                .chapters
+                .iter()
+                .map(|chapter| (chapter.chapter as u16, &chapter.verses))
+                .collect();
+            verses_in_reference(chapters, reference)
+                .into_iter()
+                .map(|(c, v, verse)| (make_path(book_index as u8, c, v), verse))
+                .collect()
+        },
+        None => vec![],
+    }
+}
+
+fn reference_verses(reference: &reference::Reference) -> Vec<(VersePath, &'static scripture_types::Verse)> {
+    match reference.collection {
+        reference::Collection::OT => book_reference_verses(&old_testament().books, reference, VersePath::PathOT),
+        reference::Collection::NT => book_reference_verses(&new_testament().books, reference, VersePath::PathNT),
+        reference::Collection::BoM => book_reference_verses(&book_of_mormon().books, reference, VersePath::PathBoM),
+        reference::Collection::PoGP => book_reference_verses(&pearl_of_great_price().books, reference, VersePath::PathPOGP),
+        reference::Collection::DC => {
+            let sections = doctrine_and_covenants()
+                .sections
+                .iter()
+                .map(|section| (section.section as u16, &section.verses))
+                .collect();
+            verses_in_reference(sections, reference)
+                .into_iter()
+                .map(|(s, v, verse)| (VersePath::PathDC(s, v), verse))
+                .collect()
         },
+    }
+}
+
+// Every posting list each word of a search resolves to. Words are resolved
+// once per search, however many times the query and its scoring use them.
+type ResolvedTerms<'a> = FnvHashMap<&'a str, Vec<terms::Usages>>;
+
+fn resolve_terms<'a>(clause: &'a query::Clause, preferences: &preferences::SearchPreferences) -> ResolvedTerms<'a> {
+    query::clause_words(clause)
+        .into_iter()
+        .map(|word| (word.as_str(), terms::resolve_term(word, preferences)))
+        .collect()
+}
+
+fn term_matches(term: &str, resolved: &ResolvedTerms) -> Option<query::Matches> {
+    let usages = resolved.get(term)?;
+    if usages.is_empty() {
+        return None;
+    }
+
+    let matches = usages
+        .iter()
+        .flat_map(|usages| usages.entries())
+        .fold(FnvHashMap::default(), |mut acc: query::Matches, (scripture_id, highlights)| {
+            let mut highlights_vec = extract_highlights(highlights);
+            acc.entry(scripture_id)
+                .and_modify(|existing_highlights| {
+                    existing_highlights.append(&mut highlights_vec);
+                })
+                .or_insert(highlights_vec);
+            acc
+        });
+
+    Some(matches)
+}
+
+fn verse_word_ranges(scripture_id: &u16, preferences: &preferences::SearchPreferences) -> Vec<(usize, usize)> {
+    passage_text(scripture_id, preferences)
+        .map(|text| indices::get_word_ranges(text))
+        .unwrap_or_default()
+}
+
+fn phrase_matches(
+    words: &[String],
+    resolved: &ResolvedTerms,
+    preferences: &preferences::SearchPreferences,
+) -> query::Matches {
+    let word_matches: Vec<query::Matches> = match words
+        .iter()
+        .map(|word| term_matches(word, resolved))
+        .collect::<Option<Vec<query::Matches>>>()
+    {
+        Some(word_matches) => word_matches,
+        None => return FnvHashMap::default(),
+    };
+
+    let (first, rest) = match word_matches.split_first() {
+        Some(split) => split,
+        None => return FnvHashMap::default(),
+    };
+
+    first
+        .keys()
+        .filter(|scripture_id| rest.iter().all(|other| other.contains_key(*scripture_id)))
+        .filter_map(|scripture_id| {
+            let phrase_highlights: Vec<Vec<query::Highlight>> = word_matches
+                .iter()
+                .map(|matches| matches[scripture_id].clone())
+                .collect();
+            // Passages in collections that are still downloading are kept
+            // unchecked, so that the search reports those collections as
+            // pending rather than showing them.
+            if !passage_loaded(scripture_id) {
+                return Some((*scripture_id, phrase_highlights.concat()));
+            }
+            let phrases = query::find_phrases(&verse_word_ranges(scripture_id, preferences), &phrase_highlights);
+            if phrases.is_empty() {
+                None
+            } else {
+                Some((*scripture_id, phrases))
+            }
+        })
+        .collect()
+}
+
+fn near_matches(
+    left: &query::Clause,
+    right: &query::Clause,
+    distance: usize,
+    resolved: &ResolvedTerms,
+    preferences: &preferences::SearchPreferences,
+) -> query::Matches {
+    let (left_matches, right_matches) = match (
+        clause_matches(left, resolved, preferences),
+        clause_matches(right, resolved, preferences),
+    ) {
+        (Some(left_matches), Some(right_matches)) => (left_matches, right_matches),
+        _ => return FnvHashMap::default(),
     };
 
-    return_value
+    left_matches
+        .iter()
+        .filter_map(|(scripture_id, left_highlights)| {
+            let right_highlights = right_matches.get(scripture_id)?;
+            // Kept unchecked until their collection loads, as in `phrase_matches`.
+            if !passage_loaded(scripture_id) {
+                return Some((*scripture_id, [&left_highlights[..], &right_highlights[..]].concat()));
+            }
+            let near = query::find_near(
+                &verse_word_ranges(scripture_id, preferences),
+                left_highlights,
+                right_highlights,
+                distance,
+            );
+            if near.is_empty() {
+                None
+            } else {
+                Some((*scripture_id, near))
+            }
+        })
+        .collect()
+}
+
+// `None` when a lone word is missing from the index entirely.
+fn clause_matches(
+    clause: &query::Clause,
+    resolved: &ResolvedTerms,
+    preferences: &preferences::SearchPreferences,
+) -> Option<query::Matches> {
+    match clause {
+        query::Clause::Word(word) => term_matches(word, resolved),
+        query::Clause::Phrase(words) => Some(phrase_matches(words, resolved, preferences)),
+        query::Clause::Near(left, right, distance) => Some(near_matches(left, right, *distance, resolved, preferences)),
+        query::Clause::Or(alternatives) => query::combine_alternatives(
+            alternatives
+                .iter()
+                .map(|alternative| clause_matches(alternative, resolved, preferences))
+                .collect()
+        ),
+        query::Clause::Group(items) => query::combine_group(
+            items
+                .iter()
+                .map(|(occur, item)| (*occur, clause_matches(item, resolved, preferences)))
+                .collect(),
+            preferences.and,
+        ),
+    }
+}
+
+// BM25 scores for the given verses, keyed by scripture id. Every word in the
+// search counts towards the score, including words from phrases.
+fn relevance_scores(
+    clause: &query::Clause,
+    scripture_ids: &[u16],
+    resolved: &ResolvedTerms,
+    preferences: &preferences::SearchPreferences,
+) -> FnvHashMap<u16, f64> {
+    let metadata = &index().metadata;
+    let total_documents = metadata.paths.len() + metadata.heading_paths.len() + metadata.front_matter_paths.len();
+
+    let mut words = query::clause_words(clause);
+    words.sort();
+    words.dedup();
+    let word_matches: Vec<query::Matches> = words
+        .iter()
+        .filter_map(|word| term_matches(word, resolved))
+        .collect();
+
+    scripture_ids
+        .iter()
+        .map(|scripture_id| {
+            let terms: Vec<ranking::TermStats> = word_matches
+                .iter()
+                .map(|matches| ranking::TermStats {
+                    term_frequency: matches.get(scripture_id).map(|highlights| highlights.len()).unwrap_or(0),
+                    document_frequency: matches.len(),
+                })
+                .collect();
+            let verse_length = verse_word_ranges(scripture_id, preferences).len();
+            let score = ranking::bm25(&terms, verse_length, metadata.average_verse_length, total_documents);
+            (*scripture_id, score)
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct Suggestion {
+    pub word: String,
+    pub alternatives: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResults<'a> {
+    pub results: Vec<&'a String>,
+    pub suggestions: Vec<Suggestion>,
+    // Collections with hits that can't be shown until they're loaded. A
+    // reference names its collection even when it isn't included, so that
+    // it's fetched just for that reference.
+    pub pending: Vec<&'static str>,
+    // A search that reads as a reference to verses that don't exist.
+    #[serde(rename = "invalidReference")]
+    pub invalid_reference: Option<String>,
+}
+
+// Spelling suggestions for search words that match nothing at all.
+fn suggestions_for(clause: &query::Clause, resolved: &ResolvedTerms) -> Vec<Suggestion> {
+    let mut suggestions: Vec<Suggestion> = vec![];
+    // Excluded words can't be misspellings of anything the user is after.
+    for word in query::wanted_words(clause) {
+        if word.contains('*') || suggestions.iter().any(|suggestion| &suggestion.word == word) {
+            continue;
+        }
+        if resolved.get(word.as_str()).map(|usages| !usages.is_empty()).unwrap_or(true) {
+            continue;
+        }
+        let alternatives = terms::suggest(word);
+        if !alternatives.is_empty() {
+            suggestions.push(Suggestion {
+                word: word.to_string(),
+                alternatives,
+            });
+        }
+    }
+    suggestions
 }
 
-pub type WordsIndexBorrowing = FnvHashMap<String, &'static phf::Map<u16, (U256,u128)>>;
 #[wasm_bindgen]
 pub fn full_match_search(search_term_raw: String, search_preferences_js: JsValue) -> JsValue {
     let t_0 = web_sys::window().unwrap().performance().unwrap().now();
 
     let search_preferences: preferences::SearchPreferences = search_preferences_js.into_serde().unwrap();
     if !preferences::check_can_search(&search_term_raw, &search_preferences) {
-        let no_results: Vec<String> = vec![];
+        let no_results = SearchResults {
+            results: vec![],
+            suggestions: vec![],
+            pending: vec![],
+            invalid_reference: None,
+        };
         return JsValue::from_serde(&no_results).unwrap();
     }
 
-    log!("accessing paths index");
-    let paths_index = &indices::PHF_PATHS_INDEX;
-
-    let search_term = &make_splittable(&search_term_raw.to_lowercase());
+    // A reference like "Alma 32:21-23" shows those verses rather than
+    // verses that happen to contain the words "alma", "32" and so on.
+    // References show their verses whether or not the collection is
+    // included.
+    let reference = reference::parse_reference(&search_term_raw);
+    if let Some(collection) = reference.as_ref().map(|reference| reference.collection) {
+        if !is_loaded(collection) {
+            return JsValue::from_serde(&SearchResults {
+                results: vec![],
+                suggestions: vec![],
+                pending: vec![collection_key(collection)],
+                invalid_reference: None,
+            }).unwrap();
+        }
+    }
+    // References to verses that don't exist, like "Alma 32:23-21" or
+    // "Alma 70", are reported rather than searched for as words.
+    if let Some(reference) = reference {
+        let formatted: Vec<String> = reference_verses(&reference)
+            .iter()
+            .map(|(verse_path, verse)| format_verse(verse_path, verse, &[]))
+            .collect();
+        return JsValue::from_serde(&SearchResults {
+            results: formatted.iter().collect(),
+            suggestions: vec![],
+            pending: vec![],
+            invalid_reference: if formatted.is_empty() { Some(search_term_raw.trim().to_string()) } else { None },
+        }).unwrap();
+    }
 
-    let search_stems: FnvHashSet<_> = search_term
-        .split_whitespace()
-        .map(|term| STEMMER.stem(term).to_string())
-        .collect();
+    log!("accessing paths index");
+    let paths_index = &index().metadata.paths;
 
-    let possible_matches: WordsIndexBorrowing =
-        search_stems
-            .iter()
-            .fold(FnvHashMap::default(), |mut matching_index, term| {
-                if let Some(v) = indices::PHF_WORDS_INDEX.get(term.as_str()) {
-                    matching_index.insert(term.to_string(), v);
-                }
-                matching_index
-            });
+    // Words are only lowercased once they reach the index lookup, so that
+    // the `OR` operator can be told apart from the word "or".
+    let clause = query::parse_query(&search_term_raw);
+    let resolved: ResolvedTerms = clause
+        .as_ref()
+        .map(|clause| resolve_terms(clause, &search_preferences))
+        .unwrap_or_default();
+    let matches: query::Matches = clause
+        .as_ref()
+        .and_then(|clause| clause_matches(clause, &resolved, &search_preferences))
+        .unwrap_or_default();
+    let suggestions = clause
+        .as_ref()
+        .map(|clause| suggestions_for(clause, &resolved))
+        .unwrap_or_default();
 
     let verse_paths_index = &*VERSE_PATHS_INDEX;
 
-    log!("about to use paths index");
-    let or_matches: FnvHashSet<u16> = possible_matches
-        .iter()
-        .flat_map(|(_k, v)| v.keys())
-        .map(|x| *x)
-        .filter(|x| check_collection_searchable(paths_index.get(x).unwrap(), &search_preferences))
-        .collect();
-
-    let and_matches: FnvHashSet<u16> = possible_matches.iter().fold(or_matches, |acc, (_k, v)| {
-        let current_matches: FnvHashSet<u16> = v.keys().map(|x| *x).collect();
-        let result: FnvHashSet<u16> = acc.intersection(&current_matches).map(|x| *x).collect();
-        result
-    });
+    // Hits in collections that haven't downloaded yet can't be shown or
+    // filtered by book, so they only mark their collection as pending.
+    let mut pending: Vec<reference::Collection> = vec![];
+    let mut loaded = |collection: reference::Collection| -> bool {
+        if is_loaded(collection) {
+            return true;
+        }
+        if source_included(collection, &search_preferences) && !pending.contains(&collection) {
+            pending.push(collection);
+        }
+        false
+    };
 
-    let mut verses: Vec<(u16, String)> = possible_matches
-        .iter()
-        .flat_map(|(_k, v)| v.entries().filter(|x| and_matches.contains(x.0)))
-        .map(|(scripture_id, highlights)| {
-            let verse_path = paths_index.get(scripture_id).unwrap();
-            (verse_path, highlights)
-        })
-        .fold(
-            FnvHashMap::default(),
-            |mut acc: FnvHashMap<&VersePath, Vec<(u16, u8)>>, (verse_path, highlights)| {
-                let mut highlights_vec = extract_highlights(&highlights);
-                acc.entry(verse_path)
-                    .and_modify(|existing_highlights| {
-                        existing_highlights.append(&mut highlights_vec);
-                    })
-                    .or_insert(highlights_vec);
-                acc
-            },
-        )
+    log!("about to use paths index");
+    // Each hit is kept with its scripture id and its place in scripture.
+    // Headings and notes sort just before the verse they point at.
+    let mut verses: Vec<(u16, (u16, u8), String)> = matches
         .iter()
-        .map(|(verse_path, highlights)| {
-            let verse = resolve_verse_path(verse_path, &search_preferences);
-            (verse_paths_index[&verse_path], format_verse(verse_path, verse, highlights))
+        .filter_map(|(scripture_id, highlights)| {
+            if let Some(verse_path) = paths_index.get(scripture_id) {
+                if !loaded(verse_path_collection(verse_path))
+                    || !check_collection_searchable(verse_path, &search_preferences)
+                {
+                    return None;
+                }
+                let verse = resolve_verse_path(verse_path, &search_preferences);
+                return Some((
+                    *scripture_id,
+                    (verse_paths_index[verse_path], VERSE_RANK),
+                    format_verse(verse_path, verse, highlights),
+                ));
+            }
+            if let Some(heading_path) = index().metadata.heading_paths.get(scripture_id) {
+                if !search_preferences.headings
+                    || !loaded(verse_path_collection(&heading_path.verse_path))
+                    || !check_collection_searchable(&heading_path.verse_path, &search_preferences)
+                {
+                    return None;
+                }
+                return Some((
+                    *scripture_id,
+                    (*verse_paths_index.get(&heading_path.verse_path)?, LEADING_RANK + 1 + heading_path.field as u8),
+                    format_heading(heading_path, &search_preferences, highlights),
+                ));
+            }
+            let front_matter_path = index().metadata.front_matter_paths.get(scripture_id)?;
+            if !loaded(front_matter_collection(front_matter_path))
+                || !check_front_matter_searchable(front_matter_path, &search_preferences)
+            {
+                return None;
+            }
+            let (anchor, rank) = front_matter_anchor(front_matter_path);
+            Some((
+                *scripture_id,
+                (*verse_paths_index.get(&anchor)?, rank),
+                format_front_matter(front_matter_path, highlights)?,
+            ))
         })
         .collect();
-    verses.sort_unstable_by(|a, b| a.cmp(b));
-    let sorted_verses: Vec<&String> = verses.iter().map(|(_, text)| text).collect();
+    match (search_preferences.sort, &clause) {
+        (preferences::SortOrder::Relevance, Some(clause)) => {
+            let scripture_ids: Vec<u16> = verses.iter().map(|(scripture_id, _, _)| *scripture_id).collect();
+            let scores = relevance_scores(clause, &scripture_ids, &resolved, &search_preferences);
+            let score = |scripture_id: &u16| scores.get(scripture_id).cloned().unwrap_or(0.0);
+            verses.sort_unstable_by(|a, b| {
+                score(&b.0)
+                    .partial_cmp(&score(&a.0))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(a.1.cmp(&b.1))
+                    .then(a.0.cmp(&b.0))
+            });
+        },
+        // Passages sharing a place, like the paragraphs of a title page, were
+        // numbered in reading order.
+        _ => verses.sort_unstable_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0))),
+    };
+    let sorted_verses: Vec<&String> = verses.iter().map(|(_, _, text)| text).collect();
 
     let t_1 = web_sys::window().unwrap().performance().unwrap().now();
     log!("search time: {:?}", t_1 - t_0);
-    JsValue::from_serde(&sorted_verses).unwrap()
+    JsValue::from_serde(&SearchResults {
+        results: sorted_verses,
+        suggestions,
+        pending: COLLECTIONS
+            .iter()
+            .filter(|collection| pending.contains(collection))
+            .map(|collection| collection_key(*collection))
+            .collect(),
+        invalid_reference: None,
+    }).unwrap()
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scripture_types::WordsIndex;
+    use serde_json::json;
+
+    // The Book of Mormon is loaded and the Old Testament isn't. Passages 0
+    // and 1 read "and it came to pass", and passage 2 "to pass and it then
+    // came".
+    fn load_part_of_the_canon() {
+        let mut words: WordsIndex = FnvHashMap::default();
+        let postings = [
+            ("came", [(7, 4), (7, 4), (20, 4)]),
+            ("to", [(12, 2), (12, 2), (0, 2)]),
+            ("pass", [(15, 4), (15, 4), (3, 4)]),
+        ];
+        for (word, highlights) in postings.iter() {
+            let usages = words.entry(word.to_string()).or_default();
+            for (scripture_id, highlight) in highlights.iter().enumerate() {
+                usages.insert(scripture_id as u16, vec![*highlight]);
+            }
+        }
+        let mut metadata = indices::IndexMetadata::default();
+        metadata.paths.insert(0, VersePath::PathBoM(0, 0, 0));
+        metadata.paths.insert(1, VersePath::PathOT(0, 0, 0));
+        metadata.paths.insert(2, VersePath::PathBoM(0, 0, 1));
+        let bytes = indices::write_index(&words, &FnvHashMap::default(), &metadata).unwrap();
+        let index = indices::Index::from_bytes(Box::leak(bytes.into_boxed_slice())).unwrap();
+        *INDEX.write().unwrap() = Some(Box::leak(Box::new(index)));
+
+        let verse = |verse: u16, text: &str| json!({
+            "heading": null, "pilcrow": null, "reference": format!("1 Nephi 1:{}", verse),
+            "subheading": null, "text": text, "verse": verse,
+        });
+        let book_of_mormon: BookOfMormon = serde_json::from_value(json!({
+            "books": [{
+                "book": "1 Nephi",
+                "chapters": [{
+                    "chapter": 1, "heading": null, "note": null, "reference": "1 Nephi 1",
+                    "verses": [verse(1, "and it came to pass"), verse(2, "to pass and it then came")],
+                }],
+                "facsimiles": null, "full_subtitle": null, "full_title": "The First Book of Nephi",
+                "heading": null, "lds_slug": "1-ne", "note": null,
+            }],
+            "last_modified": "2019-11-08", "lds_slug": "bofm", "subtitle": "", "testimonies": [],
+            "title": "The Book of Mormon",
+            "title_page": { "subtitle": "", "text": [], "title": "", "translated_by": "" },
+            "version": 1,
+        })).unwrap();
+        CORPORA.write().unwrap().bom = Some(Box::leak(Box::new(book_of_mormon)));
+    }
+
+    fn matching_ids(search: &str, preferences: &preferences::SearchPreferences) -> Vec<u16> {
+        let clause = query::parse_query(search).unwrap();
+        let resolved = resolve_terms(&clause, preferences);
+        let mut ids: Vec<u16> = clause_matches(&clause, &resolved, preferences).unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn phrases_and_near_leave_unloaded_collections_unchecked() {
+        load_part_of_the_canon();
+        let preferences = preferences::make_empty_preferences();
+
+        assert_eq!(None, passage_text(&1, &preferences));
+        assert_eq!(vec![0, 1], matching_ids("\"came to pass\"", &preferences));
+        assert_eq!(vec![0, 1], matching_ids("came NEAR/2 pass", &preferences));
+    }
+}
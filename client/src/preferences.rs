@@ -11,12 +11,33 @@ pub struct SearchPreferences {
     #[serde(rename = "caseSensitive")]
     pub case_sensitive: bool,
     pub exact: bool,
+    // Also match words within a few typos of each search word.
+    #[serde(default)]
+    pub fuzzy: bool,
+    // Also match archaic or modern equivalents, like "hath" for "has".
+    #[serde(default)]
+    pub synonyms: bool,
+    #[serde(default)]
+    pub sort: SortOrder,
+    // Also search verse, chapter and book headings and notes.
+    #[serde(default)]
+    pub headings: bool,
     #[serde(rename = "includedSources")]
     pub included_sources: IncludedSources,
     #[serde(rename = "includedBooks")]
     pub included_books: IncludedBooks,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    // Scripture order, from Genesis through the Pearl of Great Price.
+    #[default]
+    Canonical,
+    // Best matches first, ranked with BM25.
+    Relevance,
+}
+
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct IncludedSources {
@@ -37,11 +58,17 @@ pub struct IncludedBooks {
     pub pogp: Vec<String>,
 }
 
+// Used to warm up the indices at startup. Searches match the web app's
+// defaults, so the warm-up takes the same path as a first real search.
 pub fn make_empty_preferences() -> SearchPreferences {
     SearchPreferences {
-        and: false,
-        case_sensitive: true,
+        and: true,
+        case_sensitive: false,
         exact: false,
+        fuzzy: false,
+        synonyms: false,
+        sort: SortOrder::Canonical,
+        headings: true,
         included_sources: IncludedSources {
             ot: true,
             nt: true,
@@ -94,6 +121,10 @@ mod tests {
             and: false,
             case_sensitive: true,
             exact: false,
+            fuzzy: false,
+            synonyms: false,
+            sort: SortOrder::Canonical,
+            headings: false,
             included_sources: IncludedSources {
                 ot: true,
                 nt: true,
@@ -119,6 +150,10 @@ mod tests {
             and: false,
             case_sensitive: true,
             exact: false,
+            fuzzy: false,
+            synonyms: false,
+            sort: SortOrder::Canonical,
+            headings: false,
             included_sources: IncludedSources {
                 ot: false,
                 nt: false,
@@ -144,6 +179,10 @@ mod tests {
             and: false,
             case_sensitive: true,
             exact: false,
+            fuzzy: false,
+            synonyms: false,
+            sort: SortOrder::Canonical,
+            headings: false,
             included_sources: IncludedSources {
                 ot: true,
                 nt: true,
@@ -169,6 +208,10 @@ mod tests {
             and: false,
             case_sensitive: true,
             exact: false,
+            fuzzy: false,
+            synonyms: false,
+            sort: SortOrder::Canonical,
+            headings: false,
             included_sources: IncludedSources {
                 ot: false,
                 nt: false,
@@ -0,0 +1,672 @@
+use fnv::FnvHashMap;
+
+use crate::make_splittable;
+
+// (byte offset, byte length) of a highlighted span within a verse.
+pub type Highlight = (usize, usize);
+// Highlights within each matching verse, keyed by scripture id.
+pub type Matches = FnvHashMap<u16, Vec<Highlight>>;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Occur {
+    // `+word`
+    Must,
+    // A bare word, joined to its neighbors by the `and` preference.
+    Should,
+    // `-word`
+    MustNot,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Clause {
+    Word(String),
+    // Words that must appear adjacent and in order.
+    Phrase(Vec<String>),
+    // Two clauses within the given number of words of each other.
+    Near(Box<Clause>, Box<Clause>, usize),
+    // Clauses separated by `OR`.
+    Or(Vec<Clause>),
+    // A run of clauses side by side.
+    Group(Vec<(Occur, Clause)>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(Vec<String>),
+    Near(usize),
+    Or,
+    Plus,
+    Minus,
+    OpenParen,
+    CloseParen,
+}
+
+fn split_words(text: &str) -> Vec<String> {
+    make_splittable(&text.to_string())
+        .split_whitespace()
+        .map(|word| word.to_string())
+        .collect()
+}
+
+// Reads a `NEAR/n` operator, in any case.
+fn parse_near(token: &str) -> Option<usize> {
+    let mut parts = token.splitn(2, '/');
+    let operator = parts.next()?;
+    if !operator.eq_ignore_ascii_case("near") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+fn tokenize(raw: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut chars = raw.chars().peekable();
+    let mut depth = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // An unterminated quote runs to the end of the search.
+                let quoted: String = chars.by_ref().take_while(|c| *c != '"').collect();
+                let mut words = split_words(&quoted);
+                if words.len() > 1 {
+                    tokens.push(Token::Phrase(words));
+                } else if let Some(word) = words.pop() {
+                    tokens.push(Token::Word(word));
+                }
+            },
+            '(' => {
+                depth += 1;
+                tokens.push(Token::OpenParen);
+            },
+            ')' => {
+                // Unbalanced closing parens are dropped.
+                if depth > 0 {
+                    depth -= 1;
+                    tokens.push(Token::CloseParen);
+                }
+            },
+            // Only an operator when it's right up against what it applies
+            // to, so that the `-` in `faith - works` is dropped.
+            '+' | '-' => {
+                let starts_clause = match chars.peek() {
+                    Some(next) => !next.is_whitespace() && !matches!(next, ')' | '+' | '-'),
+                    None => false,
+                };
+                if starts_clause {
+                    tokens.push(if c == '+' { Token::Plus } else { Token::Minus });
+                }
+            },
+            c if c.is_whitespace() => {},
+            c => {
+                let mut bare = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || *next == '"' || *next == '(' || *next == ')' {
+                        break;
+                    }
+                    bare.push(*next);
+                    chars.next();
+                }
+
+                if bare == "OR" {
+                    tokens.push(Token::Or);
+                } else if let Some(distance) = parse_near(&bare) {
+                    tokens.push(Token::Near(distance));
+                } else {
+                    tokens.extend(split_words(&bare).into_iter().map(Token::Word));
+                }
+            },
+        };
+    }
+
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) {
+        self.position += 1;
+    }
+
+    fn parse_or(&mut self) -> Option<Clause> {
+        let mut alternatives: Vec<Clause> = vec![];
+        loop {
+            alternatives.extend(self.parse_group());
+            match self.peek() {
+                Some(Token::Or) => self.advance(),
+                _ => break,
+            };
+        }
+
+        match alternatives.len() {
+            0 => None,
+            1 => alternatives.pop(),
+            _ => Some(Clause::Or(alternatives)),
+        }
+    }
+
+    fn parse_group(&mut self) -> Option<Clause> {
+        let mut items: Vec<(Occur, Clause)> = vec![];
+        loop {
+            let occur = match self.peek() {
+                None | Some(Token::Or) | Some(Token::CloseParen) => break,
+                // A NEAR with nothing on its left.
+                Some(Token::Near(_)) => {
+                    self.advance();
+                    continue;
+                },
+                Some(Token::Plus) => {
+                    self.advance();
+                    Occur::Must
+                },
+                Some(Token::Minus) => {
+                    self.advance();
+                    Occur::MustNot
+                },
+                _ => Occur::Should,
+            };
+            if let Some(clause) = self.parse_near() {
+                items.push((occur, clause));
+            }
+        }
+
+        match items.len() {
+            0 => None,
+            1 if items[0].0 == Occur::Should => items.pop().map(|(_, clause)| clause),
+            _ => Some(Clause::Group(items)),
+        }
+    }
+
+    fn parse_near(&mut self) -> Option<Clause> {
+        let mut clause = self.parse_primary()?;
+        while let Some(Token::Near(distance)) = self.peek() {
+            let distance = *distance;
+            self.advance();
+            match self.parse_primary() {
+                Some(right) => {
+                    clause = Clause::Near(Box::new(clause), Box::new(right), distance);
+                },
+                None => break,
+            };
+        }
+        Some(clause)
+    }
+
+    // Leaves the position alone unless the next token starts a clause.
+    fn parse_primary(&mut self) -> Option<Clause> {
+        match self.peek()? {
+            Token::Word(word) => {
+                let clause = Clause::Word(word.to_string());
+                self.advance();
+                Some(clause)
+            },
+            Token::Phrase(words) => {
+                let clause = Clause::Phrase(words.clone());
+                self.advance();
+                Some(clause)
+            },
+            Token::OpenParen => {
+                self.advance();
+                let inner = self.parse_or();
+                // An unclosed paren runs to the end of the search.
+                if let Some(Token::CloseParen) = self.peek() {
+                    self.advance();
+                }
+                inner
+            },
+            _ => None,
+        }
+    }
+}
+
+// Parses a raw search. Bare words are joined by the `and` preference,
+// `+word` is required, `-word` is excluded, `OR` separates alternatives,
+// parentheses group, double quotes make a phrase and `NEAR/n` joins the
+// clauses on either side of it. Malformed pieces are dropped rather than
+// failing the whole search.
+pub fn parse_query(raw: &str) -> Option<Clause> {
+    let mut parser = Parser {
+        tokens: tokenize(raw),
+        position: 0,
+    };
+    parser.parse_or()
+}
+
+// Every word searched for by a clause, including excluded ones.
+pub fn clause_words(clause: &Clause) -> Vec<&String> {
+    match clause {
+        Clause::Word(word) => vec![word],
+        Clause::Phrase(words) => words.iter().collect(),
+        Clause::Near(left, right, _) => {
+            let mut words = clause_words(left);
+            words.extend(clause_words(right));
+            words
+        },
+        Clause::Or(alternatives) => alternatives.iter().flat_map(clause_words).collect(),
+        Clause::Group(items) => items.iter().flat_map(|(_, item)| clause_words(item)).collect(),
+    }
+}
+
+// The words a search is looking for: every clause word, except those in
+// clauses excluded with `-`.
+pub fn wanted_words(clause: &Clause) -> Vec<&String> {
+    match clause {
+        Clause::Near(left, right, _) => {
+            let mut words = wanted_words(left);
+            words.extend(wanted_words(right));
+            words
+        },
+        Clause::Or(alternatives) => alternatives.iter().flat_map(wanted_words).collect(),
+        Clause::Group(items) => items
+            .iter()
+            .filter(|(occur, _)| *occur != Occur::MustNot)
+            .flat_map(|(_, item)| wanted_words(item))
+            .collect(),
+        _ => clause_words(clause),
+    }
+}
+
+// Index of the word in `word_ranges` that contains the byte at `offset`.
+fn word_ordinal(word_ranges: &[(usize, usize)], offset: usize) -> usize {
+    match word_ranges.binary_search_by_key(&offset, |(from, _)| *from) {
+        Ok(ordinal) => ordinal,
+        Err(ordinal) => ordinal.saturating_sub(1),
+    }
+}
+
+// Finds every highlight on either side that lies within `distance` words of
+// a highlight on the other side, in either order.
+pub fn find_near(word_ranges: &[(usize, usize)], left: &[Highlight], right: &[Highlight], distance: usize) -> Vec<Highlight> {
+    let span = |(start, length): &Highlight| {
+        let from = word_ordinal(word_ranges, *start);
+        let to = word_ordinal(word_ranges, *start + *length - 1);
+        (from, to)
+    };
+    let is_near = |a: &Highlight, b: &Highlight| {
+        let (a_from, a_to) = span(a);
+        let (b_from, b_to) = span(b);
+        if a_to < b_from {
+            b_from - a_to <= distance
+        } else if b_to < a_from {
+            a_from - b_to <= distance
+        } else {
+            true
+        }
+    };
+
+    let mut near: Vec<Highlight> = left
+        .iter()
+        .filter(|a| right.iter().any(|b| is_near(a, b)))
+        .chain(right.iter().filter(|b| left.iter().any(|a| is_near(a, b))))
+        .cloned()
+        .collect();
+    near.sort();
+    near
+}
+
+// Finds every place in a verse where the phrase words occur one right after
+// another. `word_ranges` are the verse's word boundaries, and
+// `phrase_highlights` holds each phrase word's highlights within the verse.
+// Each match is returned as a single highlight spanning the whole phrase.
+pub fn find_phrases(word_ranges: &[(usize, usize)], phrase_highlights: &[Vec<Highlight>]) -> Vec<Highlight> {
+    let ordinal_of = |(start, _): &Highlight| {
+        word_ranges
+            .binary_search_by_key(start, |(from, _)| *from)
+            .ok()
+    };
+
+    let ordinals: Vec<FnvHashMap<usize, Highlight>> = phrase_highlights
+        .iter()
+        .map(|highlights| {
+            highlights
+                .iter()
+                .filter_map(|highlight| ordinal_of(highlight).map(|ordinal| (ordinal, *highlight)))
+                .collect()
+        })
+        .collect();
+
+    let (first, rest) = match ordinals.split_first() {
+        Some(split) => split,
+        None => return vec![],
+    };
+
+    let mut phrases: Vec<Highlight> = first
+        .iter()
+        .filter_map(|(ordinal, (start, length))| {
+            let mut end = *start + *length;
+            for (offset, word) in rest.iter().enumerate() {
+                let (next_start, next_length) = word.get(&(ordinal + offset + 1))?;
+                end = next_start + next_length;
+            }
+            Some((*start, end - *start))
+        })
+        .collect();
+    phrases.sort();
+    phrases
+}
+
+// Sorts highlights and joins any that overlap, so that each part of a verse
+// is wrapped at most once.
+pub fn merge_highlights(highlights: &[Highlight]) -> Vec<Highlight> {
+    let mut sorted = highlights.to_vec();
+    sorted.sort();
+
+    sorted.into_iter().fold(vec![], |mut acc: Vec<Highlight>, (start, length)| {
+        match acc.last_mut() {
+            Some((prev_start, prev_length)) if start < *prev_start + *prev_length => {
+                let end = std::cmp::max(*prev_start + *prev_length, start + length);
+                *prev_length = end - *prev_start;
+            },
+            _ => acc.push((start, length)),
+        };
+        acc
+    })
+}
+
+// Combines the clauses of a group. `None` marks a lone word missing from
+// the index, which is left out of the search unless it was required.
+pub fn combine_group(items: Vec<(Occur, Option<Matches>)>, and: bool) -> Option<Matches> {
+    let mut required: Vec<Matches> = vec![];
+    let mut optional: Vec<Matches> = vec![];
+    let mut excluded: Vec<Matches> = vec![];
+
+    for (occur, matches) in items {
+        match (occur, matches) {
+            (Occur::Must, matches) => required.push(matches.unwrap_or_default()),
+            (Occur::Should, Some(matches)) => if and {
+                required.push(matches);
+            } else {
+                optional.push(matches);
+            },
+            (Occur::MustNot, Some(matches)) => excluded.push(matches),
+            (_, None) => {},
+        };
+    }
+
+    let included: Matches = if !required.is_empty() {
+        let mut required_iter = required.into_iter();
+        let first = required_iter.next().unwrap_or_default();
+        let must = required_iter.fold(first, intersect_matches);
+        // Optional clauses only add highlights to verses that already match.
+        optional.into_iter().fold(must, |mut acc, matches| {
+            for (id, mut highlights) in matches {
+                if let Some(existing_highlights) = acc.get_mut(&id) {
+                    existing_highlights.append(&mut highlights);
+                }
+            }
+            acc
+        })
+    } else if !optional.is_empty() {
+        optional.into_iter().fold(FnvHashMap::default(), union_matches)
+    } else {
+        return None;
+    };
+
+    let result = excluded.iter().fold(included, |mut acc, matches| {
+        for id in matches.keys() {
+            acc.remove(id);
+        }
+        acc
+    });
+    Some(result)
+}
+
+pub fn combine_alternatives(alternatives: Vec<Option<Matches>>) -> Option<Matches> {
+    alternatives
+        .into_iter()
+        .fold(None, |acc, matches| match (acc, matches) {
+            (Some(acc), Some(matches)) => Some(union_matches(acc, matches)),
+            (acc, matches) => acc.or(matches),
+        })
+}
+
+pub fn intersect_matches(a: Matches, mut b: Matches) -> Matches {
+    a
+        .into_iter()
+        .filter_map(|(id, mut highlights)| {
+            let mut other = b.remove(&id)?;
+            highlights.append(&mut other);
+            Some((id, highlights))
+        })
+        .collect()
+}
+
+pub fn union_matches(a: Matches, b: Matches) -> Matches {
+    b.into_iter().fold(a, |mut acc, (id, mut highlights)| {
+        acc.entry(id)
+            .and_modify(|existing_highlights| {
+                existing_highlights.append(&mut highlights);
+            })
+            .or_insert(highlights);
+        acc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(w: &str) -> Clause {
+        Clause::Word(String::from(w))
+    }
+
+    fn phrase(ws: Vec<&str>) -> Clause {
+        Clause::Phrase(ws.iter().map(|w| w.to_string()).collect())
+    }
+
+    fn near(left: Clause, right: Clause, distance: usize) -> Clause {
+        Clause::Near(Box::new(left), Box::new(right), distance)
+    }
+
+    fn matches(entries: Vec<(u16, Vec<Highlight>)>) -> Matches {
+        entries.into_iter().collect()
+    }
+
+    #[test]
+    fn parses_unquoted_words() {
+        assert_eq!(Some(word("faith")), parse_query("faith"));
+        assert_eq!(
+            Some(Clause::Group(vec![(Occur::Should, word("faith")), (Occur::Should, word("hope"))])),
+            parse_query("faith hope"),
+        );
+        assert_eq!(None, parse_query("  "));
+    }
+
+    #[test]
+    fn parses_quoted_phrases() {
+        assert_eq!(
+            Some(Clause::Group(vec![
+                (Occur::Should, word("behold")),
+                (Occur::Should, phrase(vec!["and", "it", "came", "to", "pass"])),
+                (Occur::Should, word("nephi")),
+            ])),
+            parse_query("behold \"and it came to pass\" nephi"),
+        );
+    }
+
+    #[test]
+    fn parses_unterminated_and_single_word_quotes() {
+        assert_eq!(
+            Some(Clause::Group(vec![(Occur::Should, word("faith")), (Occur::Should, phrase(vec!["it", "came"]))])),
+            parse_query("faith \"it came"),
+        );
+        assert_eq!(Some(word("faith")), parse_query("\"faith\""));
+        assert_eq!(None, parse_query("\"\""));
+    }
+
+    #[test]
+    fn parses_near_operators() {
+        assert_eq!(Some(near(word("faith"), word("works"), 5)), parse_query("faith NEAR/5 works"));
+        assert_eq!(
+            Some(Clause::Group(vec![
+                (Occur::Should, word("by")),
+                (Occur::Should, near(word("faith"), phrase(vec!["good", "works"]), 3)),
+            ])),
+            parse_query("by faith near/3 \"good works\""),
+        );
+    }
+
+    #[test]
+    fn ignores_near_operators_missing_a_side() {
+        assert_eq!(Some(word("faith")), parse_query("NEAR/5 faith"));
+        assert_eq!(Some(word("faith")), parse_query("faith NEAR/5"));
+        assert_eq!(
+            Some(Clause::Group(vec![
+                (Occur::Should, word("faith")),
+                (Occur::Should, word("NEAR")),
+                (Occur::Should, word("works")),
+            ])),
+            parse_query("faith NEAR works"),
+        );
+    }
+
+    #[test]
+    fn parses_required_and_excluded_clauses() {
+        assert_eq!(
+            Some(Clause::Group(vec![
+                (Occur::Must, word("faith")),
+                (Occur::MustNot, word("works")),
+                (Occur::Should, word("anti-nephi-lehies")),
+            ])),
+            parse_query("+faith -works anti-nephi-lehies"),
+        );
+        assert_eq!(Some(Clause::Group(vec![(Occur::MustNot, word("works"))])), parse_query("-works"));
+        assert_eq!(
+            Some(Clause::Group(vec![(Occur::Must, phrase(vec!["it", "came"])), (Occur::MustNot, word("works"))])),
+            parse_query("+\"it came\" -works"),
+        );
+    }
+
+    #[test]
+    fn drops_operators_that_stand_alone() {
+        assert_eq!(
+            Some(Clause::Group(vec![(Occur::Should, word("faith")), (Occur::Should, word("works"))])),
+            parse_query("faith - works"),
+        );
+        assert_eq!(
+            Some(Clause::Group(vec![(Occur::Should, word("faith")), (Occur::Should, word("works"))])),
+            parse_query("faith + works -"),
+        );
+    }
+
+    #[test]
+    fn parses_or_and_parentheses() {
+        assert_eq!(
+            Some(Clause::Group(vec![
+                (Occur::Should, Clause::Or(vec![word("faith"), word("hope")])),
+                (Occur::MustNot, word("works")),
+            ])),
+            parse_query("(faith OR hope) -works"),
+        );
+        assert_eq!(
+            Some(Clause::Or(vec![
+                Clause::Group(vec![(Occur::Should, word("faith")), (Occur::Should, word("hope"))]),
+                word("charity"),
+            ])),
+            parse_query("faith hope OR charity"),
+        );
+        assert_eq!(
+            Some(Clause::Group(vec![(Occur::Should, word("faith")), (Occur::Should, word("or")), (Occur::Should, word("hope"))])),
+            parse_query("faith or hope"),
+        );
+    }
+
+    #[test]
+    fn tolerates_unbalanced_parentheses() {
+        assert_eq!(Some(Clause::Or(vec![word("faith"), word("hope")])), parse_query("(faith OR hope"));
+        assert_eq!(
+            Some(Clause::Group(vec![(Occur::Should, word("faith")), (Occur::Should, word("hope"))])),
+            parse_query("faith) hope"),
+        );
+        assert_eq!(Some(word("faith")), parse_query("() faith OR"));
+    }
+
+    #[test]
+    fn lists_every_clause_word() {
+        let clause = parse_query("(faith OR hope) -works \"it came\" NEAR/2 pass").unwrap();
+        let words: Vec<&str> = clause_words(&clause).iter().map(|word| word.as_str()).collect();
+        assert_eq!(vec!["faith", "hope", "works", "it", "came", "pass"], words);
+    }
+
+    #[test]
+    fn leaves_excluded_words_out_of_wanted_words() {
+        let clause = parse_query("(faith OR hope) -works +\"it came\" -(pass OR by)").unwrap();
+        let words: Vec<&str> = wanted_words(&clause).iter().map(|word| word.as_str()).collect();
+        assert_eq!(vec!["faith", "hope", "it", "came"], words);
+    }
+
+    #[test]
+    fn combines_groups_with_the_and_preference() {
+        let faith = matches(vec![(1, vec![(0, 5)]), (2, vec![(0, 5)])]);
+        let hope = matches(vec![(2, vec![(10, 4)]), (3, vec![(10, 4)])]);
+
+        let and = combine_group(vec![(Occur::Should, Some(faith.clone())), (Occur::Should, Some(hope.clone()))], true);
+        assert_eq!(Some(matches(vec![(2, vec![(0, 5), (10, 4)])])), and);
+
+        let or = combine_group(vec![(Occur::Should, Some(faith.clone())), (Occur::Should, Some(hope.clone()))], false);
+        assert_eq!(vec![1, 2, 3], { let mut ids: Vec<u16> = or.unwrap().keys().cloned().collect(); ids.sort(); ids });
+    }
+
+    #[test]
+    fn combines_required_optional_and_excluded_clauses() {
+        let faith = matches(vec![(1, vec![(0, 5)]), (2, vec![(0, 5)])]);
+        let hope = matches(vec![(2, vec![(10, 4)]), (3, vec![(10, 4)])]);
+        let works = matches(vec![(1, vec![(20, 5)])]);
+
+        let result = combine_group(vec![
+            (Occur::Must, Some(faith.clone())),
+            (Occur::Should, Some(hope.clone())),
+            (Occur::MustNot, Some(works.clone())),
+        ], false);
+        assert_eq!(Some(matches(vec![(2, vec![(0, 5), (10, 4)])])), result);
+
+        assert_eq!(None, combine_group(vec![(Occur::MustNot, Some(works.clone()))], true));
+        assert_eq!(Some(matches(vec![])), combine_group(vec![(Occur::Must, None), (Occur::Should, Some(faith.clone()))], false));
+        assert_eq!(Some(faith.clone()), combine_group(vec![(Occur::Should, None), (Occur::Should, Some(faith.clone()))], true));
+    }
+
+    #[test]
+    fn finds_near_words_in_either_order() {
+        // "faith without works is dead, and works without faith"
+        let ranges = vec![(0, 5), (6, 13), (14, 19), (20, 22), (23, 27), (29, 32), (33, 38), (39, 46), (47, 52)];
+        let faith = vec![(0, 5), (47, 5)];
+        let works = vec![(14, 5), (33, 5)];
+        assert_eq!(vec![(0, 5), (14, 5), (33, 5), (47, 5)], find_near(&ranges, &faith, &works, 2));
+        assert_eq!(vec![(0, 5), (14, 5)], find_near(&ranges, &faith, &[(14, 5)], 2));
+        assert_eq!(Vec::<Highlight>::new(), find_near(&ranges, &faith, &works, 1));
+    }
+
+    #[test]
+    fn finds_adjacent_phrase_words() {
+        // "And it came to pass, that it came"
+        let ranges = vec![(0, 3), (4, 6), (7, 11), (12, 14), (15, 19), (21, 25), (26, 28), (29, 33)];
+        let it = vec![(4, 2), (26, 2)];
+        let came = vec![(7, 4), (29, 4)];
+        let to = vec![(12, 2)];
+        assert_eq!(vec![(4, 10)], find_phrases(&ranges, &[it.clone(), came.clone(), to]));
+        assert_eq!(vec![(4, 7), (26, 7)], find_phrases(&ranges, &[it, came]));
+    }
+
+    #[test]
+    fn ignores_out_of_order_phrase_words() {
+        let ranges = vec![(0, 4), (5, 9), (10, 14)];
+        let first = vec![(10, 4)];
+        let second = vec![(0, 4)];
+        assert_eq!(Vec::<Highlight>::new(), find_phrases(&ranges, &[first, second]));
+    }
+
+    #[test]
+    fn merges_overlapping_highlights() {
+        assert_eq!(vec![(0, 3), (4, 10)], merge_highlights(&[(4, 10), (0, 3), (8, 4), (4, 2)]));
+        assert_eq!(vec![(0, 3)], merge_highlights(&[(0, 3), (0, 3)]));
+    }
+}
@@ -0,0 +1,76 @@
+// Okapi BM25 tuning: how quickly repeated words stop adding to a verse's
+// score, and how much long verses are penalized.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+pub struct TermStats {
+    // Times the word occurs in the verse.
+    pub term_frequency: usize,
+    // Verses the word occurs in across all scripture.
+    pub document_frequency: usize,
+}
+
+// Words found in nearly every verse count for little; rare words count for a lot.
+pub fn inverse_document_frequency(document_frequency: usize, total_documents: usize) -> f64 {
+    let n = total_documents as f64;
+    let df = document_frequency as f64;
+    (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+}
+
+pub fn bm25(terms: &[TermStats], verse_length: usize, average_verse_length: f64, total_documents: usize) -> f64 {
+    let length_ratio = if average_verse_length > 0.0 {
+        verse_length as f64 / average_verse_length
+    } else {
+        1.0
+    };
+
+    terms
+        .iter()
+        .filter(|stats| stats.term_frequency > 0)
+        .map(|stats| {
+            let tf = stats.term_frequency as f64;
+            let idf = inverse_document_frequency(stats.document_frequency, total_documents);
+            idf * (tf * (K1 + 1.0)) / (tf + K1 * (1.0 - B + B * length_ratio))
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(term_frequency: usize, document_frequency: usize) -> TermStats {
+        TermStats {
+            term_frequency,
+            document_frequency,
+        }
+    }
+
+    #[test]
+    fn rare_words_outweigh_common_ones() {
+        assert!(inverse_document_frequency(10, 40000) > inverse_document_frequency(20000, 40000));
+        assert!(inverse_document_frequency(40000, 40000) > 0.0);
+    }
+
+    #[test]
+    fn repeated_words_score_higher_with_diminishing_returns() {
+        let once = bm25(&[stats(1, 100)], 25, 25.0, 40000);
+        let twice = bm25(&[stats(2, 100)], 25, 25.0, 40000);
+        let thrice = bm25(&[stats(3, 100)], 25, 25.0, 40000);
+        assert!(twice > once);
+        assert!(thrice - twice < twice - once);
+    }
+
+    #[test]
+    fn shorter_verses_score_higher() {
+        let short = bm25(&[stats(1, 100)], 10, 25.0, 40000);
+        let long = bm25(&[stats(1, 100)], 60, 25.0, 40000);
+        assert!(short > long);
+    }
+
+    #[test]
+    fn missing_words_add_nothing() {
+        assert_eq!(0.0, bm25(&[stats(0, 100)], 25, 25.0, 40000));
+        assert_eq!(0.0, bm25(&[], 25, 25.0, 40000));
+    }
+}
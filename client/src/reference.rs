@@ -0,0 +1,280 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Collection {
+    OT,
+    NT,
+    BoM,
+    DC,
+    PoGP,
+}
+
+struct BookName {
+    // As spelled in the scripture data.
+    name: &'static str,
+    collection: Collection,
+    abbreviations: &'static [&'static str],
+}
+
+const fn book(name: &'static str, collection: Collection, abbreviations: &'static [&'static str]) -> BookName {
+    BookName {
+        name,
+        collection,
+        abbreviations,
+    }
+}
+
+// The Doctrine and Covenants has sections rather than books, so it is
+// listed under the collection's own name.
+pub const DOCTRINE_AND_COVENANTS: &str = "Doctrine and Covenants";
+
+static BOOK_NAMES: &[BookName] = &[
+    book("Genesis", Collection::OT, &["gen", "gn"]),
+    book("Exodus", Collection::OT, &["ex", "exo", "exod"]),
+    book("Leviticus", Collection::OT, &["lev", "lv"]),
+    book("Numbers", Collection::OT, &["num", "nm"]),
+    book("Deuteronomy", Collection::OT, &["deut", "dt"]),
+    book("Joshua", Collection::OT, &["josh"]),
+    book("Judges", Collection::OT, &["judg", "jdg"]),
+    book("Ruth", Collection::OT, &[]),
+    book("1 Samuel", Collection::OT, &["1 sam"]),
+    book("2 Samuel", Collection::OT, &["2 sam"]),
+    book("1 Kings", Collection::OT, &["1 kgs"]),
+    book("2 Kings", Collection::OT, &["2 kgs"]),
+    book("1 Chronicles", Collection::OT, &["1 chr", "1 chron"]),
+    book("2 Chronicles", Collection::OT, &["2 chr", "2 chron"]),
+    book("Ezra", Collection::OT, &[]),
+    book("Nehemiah", Collection::OT, &["neh"]),
+    book("Esther", Collection::OT, &["esth"]),
+    book("Job", Collection::OT, &[]),
+    book("Psalms", Collection::OT, &["ps", "psa", "psalm"]),
+    book("Proverbs", Collection::OT, &["prov", "pr"]),
+    book("Ecclesiastes", Collection::OT, &["eccl", "eccles"]),
+    book("Solomon's Song", Collection::OT, &["song", "song of solomon", "song of songs"]),
+    book("Isaiah", Collection::OT, &["isa"]),
+    book("Jeremiah", Collection::OT, &["jer"]),
+    book("Lamentations", Collection::OT, &["lam"]),
+    book("Ezekiel", Collection::OT, &["ezek"]),
+    book("Daniel", Collection::OT, &["dan"]),
+    book("Hosea", Collection::OT, &["hos"]),
+    book("Joel", Collection::OT, &[]),
+    book("Amos", Collection::OT, &[]),
+    book("Obadiah", Collection::OT, &["obad"]),
+    book("Jonah", Collection::OT, &[]),
+    book("Micah", Collection::OT, &["mic"]),
+    book("Nahum", Collection::OT, &["nah"]),
+    book("Habakkuk", Collection::OT, &["hab"]),
+    book("Zephaniah", Collection::OT, &["zeph"]),
+    book("Haggai", Collection::OT, &["hag"]),
+    book("Zechariah", Collection::OT, &["zech"]),
+    book("Malachi", Collection::OT, &["mal"]),
+    book("Matthew", Collection::NT, &["matt", "mt"]),
+    book("Mark", Collection::NT, &["mk"]),
+    book("Luke", Collection::NT, &["lk"]),
+    book("John", Collection::NT, &["jn"]),
+    book("Acts", Collection::NT, &[]),
+    book("Romans", Collection::NT, &["rom"]),
+    book("1 Corinthians", Collection::NT, &["1 cor"]),
+    book("2 Corinthians", Collection::NT, &["2 cor"]),
+    book("Galatians", Collection::NT, &["gal"]),
+    book("Ephesians", Collection::NT, &["eph"]),
+    book("Philippians", Collection::NT, &["philip", "phil"]),
+    book("Colossians", Collection::NT, &["col"]),
+    book("1 Thessalonians", Collection::NT, &["1 thes", "1 thess"]),
+    book("2 Thessalonians", Collection::NT, &["2 thes", "2 thess"]),
+    book("1 Timothy", Collection::NT, &["1 tim"]),
+    book("2 Timothy", Collection::NT, &["2 tim"]),
+    book("Titus", Collection::NT, &[]),
+    book("Philemon", Collection::NT, &["philem"]),
+    book("Hebrews", Collection::NT, &["heb"]),
+    book("James", Collection::NT, &["jas"]),
+    book("1 Peter", Collection::NT, &["1 pet"]),
+    book("2 Peter", Collection::NT, &["2 pet"]),
+    book("1 John", Collection::NT, &["1 jn"]),
+    book("2 John", Collection::NT, &["2 jn"]),
+    book("3 John", Collection::NT, &["3 jn"]),
+    book("Jude", Collection::NT, &[]),
+    book("Revelation", Collection::NT, &["rev"]),
+    book("1 Nephi", Collection::BoM, &["1 ne", "1 nep"]),
+    book("2 Nephi", Collection::BoM, &["2 ne", "2 nep"]),
+    book("Jacob", Collection::BoM, &[]),
+    book("Enos", Collection::BoM, &[]),
+    book("Jarom", Collection::BoM, &[]),
+    book("Omni", Collection::BoM, &[]),
+    book("Words of Mormon", Collection::BoM, &["w of m", "wom"]),
+    book("Mosiah", Collection::BoM, &[]),
+    book("Alma", Collection::BoM, &[]),
+    book("Helaman", Collection::BoM, &["hel"]),
+    book("3 Nephi", Collection::BoM, &["3 ne", "3 nep"]),
+    book("4 Nephi", Collection::BoM, &["4 ne", "4 nep"]),
+    book("Mormon", Collection::BoM, &["morm"]),
+    book("Ether", Collection::BoM, &[]),
+    book("Moroni", Collection::BoM, &["moro"]),
+    book(DOCTRINE_AND_COVENANTS, Collection::DC, &["d&c", "dc", "doctrine & covenants"]),
+    book("Moses", Collection::PoGP, &[]),
+    book("Abraham", Collection::PoGP, &["abr"]),
+    book("Joseph Smith—Matthew", Collection::PoGP, &["js-m", "jsm", "joseph smith matthew"]),
+    book("Joseph Smith—History", Collection::PoGP, &["js-h", "jsh", "joseph smith history"]),
+    book("Articles of Faith", Collection::PoGP, &["a of f", "aof"]),
+];
+
+lazy_static! {
+    // book, chapter[:verse][-[chapter:]verse]
+    static ref RE_REFERENCE: Regex = Regex::new(
+        r"^\s*(?P<book>.*?[A-Za-z].*?)\.?\s*(?P<from_chapter>\d+)(?::(?P<from_verse>\d+))?(?:\s*[-–—]\s*(?:(?P<to_chapter>\d+):)?(?P<to>\d+))?\s*$"
+    ).unwrap();
+}
+
+// A span of scripture. Chapters (or D&C sections) and verses are numbered
+// from 1, as in the reference itself. A missing verse means the whole chapter.
+#[derive(Debug, PartialEq)]
+pub struct Reference {
+    pub collection: Collection,
+    pub book: &'static str,
+    pub from_chapter: u16,
+    pub from_verse: Option<u16>,
+    pub to_chapter: u16,
+    pub to_verse: Option<u16>,
+}
+
+// Drops case, spacing and punctuation so that "1 Ne.", "1ne" and "1 NE"
+// all compare equal.
+fn normalize(name: &str) -> String {
+    name
+        .to_lowercase()
+        .replace("&", "and")
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+fn find_book(name: &str) -> Option<&'static BookName> {
+    let normalized = normalize(name);
+    if normalized.is_empty() {
+        return None;
+    }
+
+    let exact = BOOK_NAMES.iter().find(|book| {
+        normalize(book.name) == normalized
+            || book.abbreviations.iter().any(|abbreviation| normalize(abbreviation) == normalized)
+    });
+    if exact.is_some() {
+        return exact;
+    }
+
+    // Otherwise accept the start of a book's name, as long as only one book
+    // starts that way and it's long enough not to be an everyday word.
+    if normalized.len() < 3 {
+        return None;
+    }
+    let mut prefixed = BOOK_NAMES
+        .iter()
+        .filter(|book| normalize(book.name).starts_with(&normalized));
+    match (prefixed.next(), prefixed.next()) {
+        (Some(book), None) => Some(book),
+        _ => None,
+    }
+}
+
+// Reads searches like "Alma 32:21-23", "1 Ne. 3:7", "D&C 4" or "Alma 32:43-33:3".
+// Returns `None` for anything else, including a book name without a chapter.
+// Chapters and verses aren't checked against the book, so "Alma 33-32" is
+// still a reference, just to no verses at all.
+pub fn parse_reference(raw: &str) -> Option<Reference> {
+    let captures = RE_REFERENCE.captures(raw)?;
+    let book = find_book(captures.name("book")?.as_str())?;
+
+    let number = |name: &str| -> Option<u16> {
+        captures.name(name).and_then(|found| found.as_str().parse().ok())
+    };
+
+    let from_chapter = number("from_chapter")?;
+    let from_verse = number("from_verse");
+    let (to_chapter, to_verse) = match (number("to_chapter"), number("to"), from_verse) {
+        (Some(to_chapter), Some(to), _) => (to_chapter, Some(to)),
+        // "Alma 32:21-23"
+        (None, Some(to), Some(_)) => (from_chapter, Some(to)),
+        // "Alma 32-33"
+        (None, Some(to), None) => (to, None),
+        _ => (from_chapter, from_verse),
+    };
+
+    Some(Reference {
+        collection: book.collection,
+        book: book.name,
+        from_chapter,
+        from_verse,
+        to_chapter,
+        to_verse,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(
+        collection: Collection,
+        book: &'static str,
+        (from_chapter, from_verse): (u16, Option<u16>),
+        (to_chapter, to_verse): (u16, Option<u16>),
+    ) -> Option<Reference> {
+        Some(Reference {
+            collection,
+            book,
+            from_chapter,
+            from_verse,
+            to_chapter,
+            to_verse,
+        })
+    }
+
+    #[test]
+    fn parses_chapters_verses_and_ranges() {
+        assert_eq!(reference(Collection::BoM, "Alma", (32, None), (32, None)), parse_reference("Alma 32"));
+        assert_eq!(reference(Collection::BoM, "Alma", (32, Some(21)), (32, Some(21))), parse_reference("Alma 32:21"));
+        assert_eq!(reference(Collection::BoM, "Alma", (32, Some(21)), (32, Some(23))), parse_reference("Alma 32:21-23"));
+        assert_eq!(reference(Collection::BoM, "Alma", (32, None), (33, None)), parse_reference("alma 32 - 33"));
+        assert_eq!(reference(Collection::BoM, "Alma", (32, Some(43)), (33, Some(3))), parse_reference("Alma 32:43-33:3"));
+    }
+
+    #[test]
+    fn parses_abbreviations_and_numbered_books() {
+        assert_eq!(reference(Collection::BoM, "1 Nephi", (3, Some(7)), (3, Some(7))), parse_reference("1 Ne. 3:7"));
+        assert_eq!(reference(Collection::BoM, "1 Nephi", (3, Some(7)), (3, Some(7))), parse_reference("1ne 3:7"));
+        assert_eq!(reference(Collection::NT, "1 John", (4, Some(8)), (4, Some(8))), parse_reference("1 John 4:8"));
+        assert_eq!(reference(Collection::NT, "John", (3, Some(16)), (3, Some(16))), parse_reference("Jn 3:16"));
+        assert_eq!(reference(Collection::PoGP, "Joseph Smith—History", (1, Some(17)), (1, Some(17))), parse_reference("JS-H 1:17"));
+        assert_eq!(reference(Collection::OT, "Solomon's Song", (2, None), (2, None)), parse_reference("Song of Solomon 2"));
+    }
+
+    #[test]
+    fn parses_doctrine_and_covenants_sections() {
+        assert_eq!(reference(Collection::DC, DOCTRINE_AND_COVENANTS, (4, None), (4, None)), parse_reference("D&C 4"));
+        assert_eq!(reference(Collection::DC, DOCTRINE_AND_COVENANTS, (88, Some(118)), (88, Some(118))), parse_reference("d & c 88:118"));
+        assert_eq!(reference(Collection::DC, DOCTRINE_AND_COVENANTS, (76, None), (76, None)), parse_reference("Doctrine and Covenants 76"));
+    }
+
+    #[test]
+    fn parses_unique_prefixes_only() {
+        assert_eq!(reference(Collection::BoM, "Helaman", (5, Some(12)), (5, Some(12))), parse_reference("Hela 5:12"));
+        assert_eq!(None, parse_reference("Mo 5"));
+    }
+
+    #[test]
+    fn ignores_ordinary_searches() {
+        assert_eq!(None, parse_reference("Alma"));
+        assert_eq!(None, parse_reference("faith 3"));
+        assert_eq!(None, parse_reference("and it came to pass"));
+        assert_eq!(None, parse_reference("32:21"));
+        assert_eq!(None, parse_reference("section 76"));
+    }
+
+    #[test]
+    fn parses_references_to_verses_that_dont_exist() {
+        assert_eq!(reference(Collection::BoM, "Alma", (0, None), (0, None)), parse_reference("Alma 0"));
+        assert_eq!(reference(Collection::BoM, "Alma", (33, None), (32, None)), parse_reference("Alma 33-32"));
+        assert_eq!(reference(Collection::BoM, "Alma", (32, Some(23)), (32, Some(21))), parse_reference("Alma 32:23-21"));
+    }
+}
@@ -0,0 +1,313 @@
+
+use crate::preferences::SearchPreferences;
+use crate::{index, STEMMER};
+
+pub type Usages = indices::Postings<'static>;
+
+// Whether `form` is cased the same as `query` for as long as the two words
+// agree letter for letter, so that "Lord" accepts "Lords" but not "lord" or "LORD".
+pub fn case_pattern_matches(query: &str, form: &str) -> bool {
+    for (q, f) in query.chars().zip(form.chars()) {
+        if q.to_lowercase().ne(f.to_lowercase()) {
+            break;
+        }
+        if q != f {
+            return false;
+        }
+    }
+    true
+}
+
+// The most surface forms a single wildcard term may expand to. The most
+// widely used forms are kept when a pattern matches more than this.
+pub const MAX_WILDCARD_EXPANSIONS: usize = 100;
+
+// Glob-style matching where `*` stands for any run of characters, including none.
+pub fn wildcard_matches(pattern: &[char], word: &[char]) -> bool {
+    let mut p = 0;
+    let mut w = 0;
+    // Position of the last `*` seen, and how much of `word` it has swallowed so far.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while w < word.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, w));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == word[w] {
+            p += 1;
+            w += 1;
+        } else if let Some((star, swallowed)) = backtrack {
+            backtrack = Some((star, swallowed + 1));
+            p = star + 1;
+            w = swallowed + 1;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// Wildcards are matched against surface forms rather than stems, so that
+// suffix patterns like `*ness` still find the words stemming strips down.
+// The index only visits the forms the pattern can still match.
+fn expand_wildcard(term: &str, preferences: &SearchPreferences) -> Vec<Usages> {
+    let fold_case = |word: &str| -> Vec<char> {
+        if preferences.case_sensitive {
+            word.chars().collect()
+        } else {
+            word.to_lowercase().chars().collect()
+        }
+    };
+
+    let pattern = fold_case(term);
+    if pattern.iter().all(|c| *c == '*') {
+        return vec![];
+    }
+
+    let mut expansions: Vec<Usages> = index()
+        .search_surface_forms(indices::Wildcard::new(term, !preferences.case_sensitive))
+        .filter(|(form, _)| wildcard_matches(&pattern, &fold_case(form)))
+        .map(|(_, usages)| usages)
+        .collect();
+    expansions.sort_by_key(|usages| std::cmp::Reverse(usages.len()));
+    expansions.truncate(MAX_WILDCARD_EXPANSIONS);
+    expansions
+}
+
+// Optimal string alignment distance: insertions, deletions, substitutions
+// and swaps of neighboring letters each count as one edit.
+pub fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in d[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = std::cmp::min(
+                std::cmp::min(d[i - 1][j] + 1, d[i][j - 1] + 1),
+                d[i - 1][j - 1] + cost,
+            );
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = std::cmp::min(d[i][j], d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+// How many edits a word of this many letters may be off by and still be
+// considered a typo rather than a different word.
+pub fn max_edit_distance(length: usize) -> usize {
+    match length {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+// Whether `b` is a near miss for `a`: close, but not identical.
+fn is_near_miss(a: &[char], b: &[char]) -> bool {
+    let max_distance = max_edit_distance(a.len());
+    if max_distance == 0 {
+        return false;
+    }
+    let length_difference = if a.len() > b.len() { a.len() - b.len() } else { b.len() - a.len() };
+    if length_difference > max_distance {
+        return false;
+    }
+    let distance = edit_distance(a, b);
+    distance > 0 && distance <= max_distance
+}
+
+// Fuzzy matching compares stems, so it ignores the `exact` and
+// `case_sensitive` preferences.
+fn fuzzy_usages(term: &str) -> Vec<Usages> {
+    let lowercased = term.to_lowercase();
+    let stemmed = STEMMER.stem(&lowercased);
+    let stem: Vec<char> = stemmed.chars().collect();
+    let near_stems = indices::EditDistance::new(&stemmed, max_edit_distance(stem.len()), false);
+
+    index()
+        .search_stems(near_stems)
+        .filter(|(other, _)| is_near_miss(&stem, &other.chars().collect::<Vec<char>>()))
+        .map(|(_, usages)| usages)
+        .collect()
+}
+
+pub const MAX_SUGGESTIONS: usize = 3;
+
+// Words from the scriptures that `term` may have been a misspelling of,
+// closest and most widely used first.
+pub fn suggest(term: &str) -> Vec<String> {
+    let lowercased_term = term.to_lowercase();
+    let word: Vec<char> = lowercased_term.chars().collect();
+    let near_forms = indices::EditDistance::new(&lowercased_term, max_edit_distance(word.len()), true);
+
+    let mut candidates: Vec<(usize, std::cmp::Reverse<usize>, String)> = index()
+        .search_surface_forms(near_forms)
+        .filter_map(|(form, usages)| {
+            let lowercased: Vec<char> = form.to_lowercase().chars().collect();
+            if is_near_miss(&word, &lowercased) {
+                Some((edit_distance(&word, &lowercased), std::cmp::Reverse(usages.len()), form))
+            } else {
+                None
+            }
+        })
+        .collect();
+    candidates.sort();
+
+    let mut suggestions: Vec<String> = vec![];
+    for (_, _, form) in candidates {
+        if suggestions.len() == MAX_SUGGESTIONS {
+            break;
+        }
+        if !suggestions.iter().any(|suggestion| suggestion.to_lowercase() == form.to_lowercase()) {
+            suggestions.push(form);
+        }
+    }
+    suggestions
+}
+
+// Every posting list a single query word should match under the given preferences.
+pub fn resolve_term(term: &str, preferences: &SearchPreferences) -> Vec<Usages> {
+    if term.contains('*') {
+        return expand_wildcard(term, preferences);
+    }
+
+    let mut usages = resolve_word(term, preferences);
+    if preferences.fuzzy {
+        usages.extend(fuzzy_usages(term));
+    }
+    if preferences.synonyms {
+        usages.extend(synonym_usages(term));
+    }
+    usages
+}
+
+// Archaic and modern equivalents are linked by stem, so like fuzzy matching
+// they ignore the `exact` and `case_sensitive` preferences.
+fn synonym_usages(term: &str) -> Vec<Usages> {
+    let lowercased = term.to_lowercase();
+    let stem = STEMMER.stem(&lowercased);
+    let equivalents = match index().metadata.synonyms.get(&*stem) {
+        Some(equivalents) => equivalents,
+        None => return vec![],
+    };
+
+    equivalents
+        .iter()
+        .filter_map(|equivalent| index().word(equivalent))
+        .collect()
+}
+
+fn resolve_word(term: &str, preferences: &SearchPreferences) -> Vec<Usages> {
+    let lowercased = term.to_lowercase();
+
+    if !preferences.exact && !preferences.case_sensitive {
+        let stem = STEMMER.stem(&lowercased);
+        return index().word(&stem).into_iter().collect();
+    }
+
+    if preferences.exact && preferences.case_sensitive {
+        return index().surface(term).into_iter().collect();
+    }
+
+    let stem = STEMMER.stem(&lowercased);
+    let forms = match index().metadata.stem_forms.get(&*stem) {
+        Some(forms) => forms,
+        None => return vec![],
+    };
+
+    forms
+        .iter()
+        .filter(|form| if preferences.exact {
+            form.to_lowercase() == lowercased
+        } else {
+            case_pattern_matches(term, form)
+        })
+        .filter_map(|form| index().surface(form))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wildcard(pattern: &str, word: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let word: Vec<char> = word.chars().collect();
+        wildcard_matches(&pattern, &word)
+    }
+
+    #[test]
+    fn wildcard_matches_prefixes_suffixes_and_infixes() {
+        assert_eq!(true, wildcard("bapti*", "baptize"));
+        assert_eq!(true, wildcard("bapti*", "bapti"));
+        assert_eq!(true, wildcard("*ness", "righteousness"));
+        assert_eq!(true, wildcard("b*ism", "baptism"));
+        assert_eq!(true, wildcard("*phi*", "Nephi"));
+        assert_eq!(true, wildcard("M*r*n*", "Moroni"));
+    }
+
+    #[test]
+    fn wildcard_rejects_non_matches() {
+        assert_eq!(false, wildcard("bapti*", "abaptize"));
+        assert_eq!(false, wildcard("*ness", "righteous"));
+        assert_eq!(false, wildcard("b*ism", "baptisms"));
+        assert_eq!(false, wildcard("nephi", "nephite"));
+    }
+
+    fn distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        edit_distance(&a, &b)
+    }
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(0, distance("moroni", "moroni"));
+        assert_eq!(1, distance("moronai", "moroni"));
+        assert_eq!(1, distance("teh", "the"));
+        assert_eq!(1, distance("nephi", "nephy"));
+        assert_eq!(2, distance("lamanite", "lamanti"));
+        assert_eq!(3, distance("", "abc"));
+    }
+
+    #[test]
+    fn near_misses_scale_with_word_length() {
+        let chars = |word: &str| word.chars().collect::<Vec<char>>();
+        assert_eq!(true, is_near_miss(&chars("moronai"), &chars("moroni")));
+        assert_eq!(true, is_near_miss(&chars("teh"), &chars("the")));
+        assert_eq!(false, is_near_miss(&chars("moroni"), &chars("moroni")));
+        assert_eq!(false, is_near_miss(&chars("of"), &chars("or")));
+        assert_eq!(false, is_near_miss(&chars("faith"), &chars("faithful")));
+    }
+
+    #[test]
+    fn case_pattern_matches_same_casing() {
+        assert_eq!(true, case_pattern_matches("Lord", "Lord"));
+        assert_eq!(true, case_pattern_matches("LORD", "LORD"));
+        assert_eq!(true, case_pattern_matches("word", "word"));
+    }
+
+    #[test]
+    fn case_pattern_matches_rejects_different_casing() {
+        assert_eq!(false, case_pattern_matches("Lord", "lord"));
+        assert_eq!(false, case_pattern_matches("Lord", "LORD"));
+        assert_eq!(false, case_pattern_matches("Word", "word"));
+    }
+
+    #[test]
+    fn case_pattern_matches_other_forms_of_the_stem() {
+        assert_eq!(true, case_pattern_matches("Lord", "Lords"));
+        assert_eq!(true, case_pattern_matches("faith", "faithful"));
+        assert_eq!(false, case_pattern_matches("Faith", "faithful"));
+    }
+}
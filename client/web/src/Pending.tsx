@@ -0,0 +1,37 @@
+import * as React from 'react';
+
+import { Collection, COLLECTION_NAMES, LoadProgress } from './corpora';
+
+interface PendingProps {
+  pending: Collection[];
+  progress: {[collection: string]: LoadProgress};
+}
+
+function percentLoaded(progress?: LoadProgress): string {
+  if (!progress || !progress.total) {
+    return '';
+  }
+  return ` (${Math.floor(100 * progress.loaded / progress.total)}%)`;
+}
+
+export default function Pending({
+  pending,
+  progress,
+}: PendingProps) {
+  if (!pending.length) {
+    return null;
+  }
+
+  return <div className="pending">
+    {pending.map(collection => {
+      const collectionProgress = progress[collection];
+      return collectionProgress && collectionProgress.error
+        ? <div key={collection} className="fail">
+          Couldn't load the {COLLECTION_NAMES[collection]}: {collectionProgress.error}
+        </div>
+        : <div key={collection}>
+          Still loading the {COLLECTION_NAMES[collection]}{percentLoaded(collectionProgress)}; more results will appear.
+        </div>;
+    })}
+  </div>
+}
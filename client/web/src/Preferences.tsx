@@ -218,6 +218,10 @@ export interface SearchPreferences {
   or: boolean;
   caseSensitive: boolean;
   exact: boolean;
+  fuzzy: boolean;
+  synonyms: boolean;
+  sort: 'canonical' | 'relevance';
+  headings: boolean;
   toSearch: SearchMaterials;
 }
 
@@ -332,7 +336,11 @@ const defaultPreferences: SearchPreferences = {
   and: true,
   or: false,
   caseSensitive: false,
-  exact: true,
+  exact: false,
+  fuzzy: false,
+  synonyms: false,
+  sort: 'canonical',
+  headings: true,
   toSearch: defaultSearchMaterial,
 };
 
@@ -362,11 +370,31 @@ function mergeConfigs<T>(a: T, b: {}, c: any = {}): T {
   }, c) as T
 }
 
+// Bump when a default changes that saved preferences should pick up, and
+// say in `migratePreferences` what to reset. Preferences saved before this
+// existed are version 1.
+const PREFERENCES_VERSION = 2;
+
+function migratePreferences(savedPreferences: any, version: number): any {
+  let migrated = savedPreferences;
+  if (version < 2) {
+    // Searches became stemmed rather than exact by default.
+    const { exact, ...rest } = migrated;
+    migrated = rest;
+  }
+  return migrated;
+}
+
 export function loadPreferences(): SearchPreferences {
-  const savedPreferences = JSON.parse(localStorage.getItem('scripturedPreferences')) as SearchPreferences;
+  const savedPreferences = JSON.parse(localStorage.getItem('scripturedPreferences'));
 
   if (savedPreferences) {
-    const mergedConfigs = mergeConfigs(defaultPreferences, savedPreferences);
+    const version = Number(localStorage.getItem('scripturedPreferencesVersion')) || 1;
+    const migrated = migratePreferences(savedPreferences, version);
+    const mergedConfigs = mergeConfigs(defaultPreferences, migrated);
+    if (version < PREFERENCES_VERSION) {
+      savePreferences(mergedConfigs);
+    }
     return mergedConfigs
   } else {
     return defaultPreferences
@@ -375,6 +403,7 @@ export function loadPreferences(): SearchPreferences {
 
 function savePreferences(preferences: SearchPreferences) {
   localStorage.setItem('scripturedPreferences', JSON.stringify(preferences));
+  localStorage.setItem('scripturedPreferencesVersion', String(PREFERENCES_VERSION));
 }
 
 const debouncedSavePreferences = debounce(savePreferences);
@@ -445,12 +474,30 @@ export default function Preferences({
         fontSize: '18px',
       }}
     >
-      {/* <div>
+      <div>
         And search: <input type="checkbox" checked={preferences.and} onChange={e => setPreferences({...preferences, and: e.target.checked})} />
       </div>
+      <div>
+        Exact word search: <input type="checkbox" checked={preferences.exact} onChange={e => setPreferences({...preferences, exact: e.target.checked})} />
+      </div>
+      <div>
+        Typo tolerant search: <input type="checkbox" checked={preferences.fuzzy} onChange={e => setPreferences({...preferences, fuzzy: e.target.checked})} />
+      </div>
+      <div>
+        Include archaic/modern variants: <input type="checkbox" checked={preferences.synonyms} onChange={e => setPreferences({...preferences, synonyms: e.target.checked})} />
+      </div>
+      <div>
+        Search headings and notes: <input type="checkbox" checked={preferences.headings} onChange={e => setPreferences({...preferences, headings: e.target.checked})} />
+      </div>
+      <div>
+        Sort results by: <select value={preferences.sort} onChange={e => setPreferences({...preferences, sort: e.target.value as SearchPreferences['sort']})}>
+          <option value="canonical">Scripture order</option>
+          <option value="relevance">Relevance</option>
+        </select>
+      </div>
       <div>
         Case sensitive search: <input type="checkbox" checked={preferences.caseSensitive} onChange={e => setPreferences({...preferences, caseSensitive: e.target.checked})} />
-      </div>*/}
+      </div>
       <BookPreferences
         bookronym="ot"
         title="Old Testament"
@@ -0,0 +1,41 @@
+import * as React from 'react';
+
+export interface Suggestion {
+  word: string;
+  alternatives: string[];
+}
+
+interface SuggestionsProps {
+  searchTerm: string;
+  setSearchTerm: (searchTerm: string) => void;
+  suggestions: Suggestion[];
+}
+
+function escapeRegExp(text: string): string {
+  return text.replace(/[.*+?^${}()|[\]\\]/g, '\\$&');
+}
+
+export default function Suggestions({
+  searchTerm,
+  setSearchTerm,
+  suggestions,
+}: SuggestionsProps) {
+  if (!suggestions.length) {
+    return null;
+  }
+
+  return <div>
+    {suggestions.map(({ word, alternatives }) => <div key={word}>
+      Did you mean {alternatives.map((alternative, i) => <React.Fragment key={alternative}>
+        {i > 0 && ', '}
+        <a
+          href="#"
+          onClick={e => {
+            e.preventDefault();
+            setSearchTerm(searchTerm.replace(new RegExp(`\\b${escapeRegExp(word)}\\b`, 'i'), alternative));
+          }}
+        >{alternative}</a>
+      </React.Fragment>)} for {word}?
+    </div>)}
+  </div>
+}
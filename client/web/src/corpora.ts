@@ -0,0 +1,98 @@
+import * as wasm from "wasm-scriptured-client";
+
+export type Collection = 'ot' | 'nt' | 'bom' | 'dc' | 'pogp';
+
+export const COLLECTIONS: Collection[] = ['ot', 'nt', 'bom', 'dc', 'pogp'];
+
+export const COLLECTION_NAMES: {[collection in Collection]: string} = {
+  ot: 'Old Testament',
+  nt: 'New Testament',
+  bom: 'Book of Mormon',
+  dc: 'Doctrine and Covenants',
+  pogp: 'Pearl of Great Price',
+};
+
+const COLLECTION_FILES: {[collection in Collection]: string} = {
+  ot: 'old-testament.json.bin.gz',
+  nt: 'new-testament.json.bin.gz',
+  bom: 'book-of-mormon.json.bin.gz',
+  dc: 'doctrine-and-covenants.json.bin.gz',
+  pogp: 'pearl-of-great-price.json.bin.gz',
+};
+
+export interface LoadProgress {
+  collection: Collection;
+  loaded: number;
+  // Null when the server doesn't say how big the file is.
+  total: null | number;
+  done: boolean;
+  // Why the collection couldn't be loaded, if it couldn't.
+  error?: string;
+}
+
+function concat(chunks: Uint8Array[], length: number): Uint8Array {
+  const bytes = new Uint8Array(length);
+  let offset = 0;
+  chunks.forEach(chunk => {
+    bytes.set(chunk, offset);
+    offset += chunk.length;
+  });
+  return bytes;
+}
+
+function fetchWithProgress(
+  url: string,
+  onProgress: (loaded: number, total: null | number) => void,
+): Promise<Uint8Array> {
+  return fetch(url).then(response => {
+    if (!response.ok) {
+      return Promise.reject(new Error(`Failed to fetch ${url}: ${response.status}`));
+    }
+    const contentLength = response.headers.get('content-length');
+    const total = contentLength ? parseInt(contentLength, 10) : null;
+    if (!response.body) {
+      return response.arrayBuffer().then(buffer => {
+        onProgress(buffer.byteLength, total);
+        return new Uint8Array(buffer);
+      });
+    }
+
+    const reader = response.body.getReader();
+    const chunks: Uint8Array[] = [];
+    let loaded = 0;
+    const read = (): Promise<Uint8Array> => reader.read().then(({ done, value }) => {
+      if (done) {
+        return concat(chunks, loaded);
+      }
+      chunks.push(value);
+      loaded += value.length;
+      onProgress(loaded, total);
+      return read();
+    });
+    return read();
+  });
+}
+
+const requested: {[collection: string]: Promise<void>} = {};
+
+// Downloads a collection and hands it to the searcher. Each collection is
+// only fetched once, however many times it's asked for.
+export function loadCollection(
+  collection: Collection,
+  onProgress: (progress: LoadProgress) => void,
+): Promise<void> {
+  if (!requested[collection]) {
+    requested[collection] = fetchWithProgress(
+      COLLECTION_FILES[collection],
+      (loaded, total) => onProgress({ collection, loaded, total, done: false }),
+    ).then(bytes => {
+      wasm.load_collection(collection, bytes);
+      onProgress({ collection, loaded: bytes.length, total: bytes.length, done: true });
+    }).catch(e => {
+      // `load_collection` throws its error messages as plain strings.
+      onProgress({ collection, loaded: 0, total: null, done: true, error: String(e) });
+      return Promise.reject(e);
+    });
+  }
+  return requested[collection];
+}
@@ -5,6 +5,9 @@ import * as wasm from "wasm-scriptured-client";
 wasm.set_panic_hook();
 
 import Form from './Form';
+import Suggestions, { Suggestion } from './Suggestions';
+import Pending from './Pending';
+import { Collection, COLLECTIONS, loadCollection, LoadProgress } from './corpora';
 import { loadPreferences, SearchPreferences } from './Preferences';
 import overtake from './overtake';
 import { debounce } from './utils';
@@ -29,12 +32,20 @@ function jsPreferencesToWasmPreferences(jsPreferences: SearchPreferences): any {
   const {
     caseSensitive,
     exact,
+    fuzzy,
+    synonyms,
+    sort,
+    headings,
     toSearch,
   } = jsPreferences;
   return {
     and: jsPreferences.and,
     caseSensitive,
     exact,
+    fuzzy,
+    synonyms,
+    sort,
+    headings,
     includedSources: toSearch.includeSource,
     includedBooks: {
       ot: Object.entries(toSearch.ot).reduce(reduceString, []),
@@ -57,6 +68,7 @@ function cachedGetElementById(id: string) {
 }
 
 let BOOTSTRAP_WAIT = 5000;
+const INDEX_URL = 'index.bin.gz';
 const SHORTEST_SEARCH_LENGTH = 2;
 
 interface CounterRef {
@@ -114,7 +126,13 @@ function App({}: AppProps) {
   const [preferences, setPreferences] = React.useState(loadPreferences());
   const [searchPending, setSearchPending] = React.useState(false);
   const [resultCount, setResultCount] = React.useState<null | number>(null);
+  const [suggestions, setSuggestions] = React.useState<Suggestion[]>([]);
+  const [pending, setPending] = React.useState<Collection[]>([]);
+  const [invalidReference, setInvalidReference] = React.useState<string | null>(null);
+  const [progress, setProgress] = React.useState<{[collection: string]: LoadProgress}>({});
+  const [loadedCount, setLoadedCount] = React.useState(0);
   const [bootstrapped, setBootstrapped] = React.useState<Bootstrapped>(Bootstrapped.N);
+  const [failure, setFailure] = React.useState('');
   const bootstrapTimeoutRef = React.useRef<number>(0);
 
   const debouncedFullTextSearch = React.useCallback(debounce((currentSearchTerm: string, preferences: SearchPreferences) => {
@@ -125,28 +143,59 @@ function App({}: AppProps) {
     const shouldSearch = currentSearchTerm.length >= SHORTEST_SEARCH_LENGTH;
     const newResults = shouldSearch
       ? wasm.full_match_search(currentSearchTerm, jsPreferencesToWasmPreferences(preferences as any))
-      : [];
-    setResultCount(shouldSearch ? newResults.length : null);
+      : { results: [], suggestions: [], pending: [], invalidReference: null };
+    setResultCount(shouldSearch ? newResults.results.length : null);
+    setSuggestions(newResults.suggestions);
+    setPending(newResults.pending);
+    setInvalidReference(newResults.invalidReference);
     setSearchPending(false);
 
-    cachedGetElementById('scriptured-results').innerHTML = newResults.join('');
+    cachedGetElementById('scriptured-results').innerHTML = newResults.results.join('');
 
   }, 350), []);
   React.useEffect(() => {
     const timeoutMethod = ((window as any).requestIdleCallback || window.setTimeout);
-    bootstrapTimeoutRef.current = timeoutMethod(
-      () => {
-        try {
-          wasm.bootstrap_searcher();
-          setBootstrapped(Bootstrapped.Y);
-        } catch(e) {
-          alert(e);
-        }
-      },
-      (window as any).requestIdleCallback ? { timeout: BOOTSTRAP_WAIT } : BOOTSTRAP_WAIT,
-    )
+    fetch(INDEX_URL)
+      .then(response => response.ok
+        ? response.arrayBuffer()
+        : Promise.reject(new Error(`Failed to fetch ${INDEX_URL}: ${response.status}`)))
+      .then(buffer => {
+        wasm.load_index(new Uint8Array(buffer));
+        bootstrapTimeoutRef.current = timeoutMethod(
+          () => {
+            try {
+              wasm.bootstrap_searcher();
+              setBootstrapped(Bootstrapped.Y);
+            } catch(e) {
+              alert(e);
+            }
+          },
+          (window as any).requestIdleCallback ? { timeout: BOOTSTRAP_WAIT } : BOOTSTRAP_WAIT,
+        )
+      })
+      .catch(e => {
+        console.error(e);
+        // `load_index` throws its error messages as plain strings.
+        setFailure(String(e));
+        setBootstrapped(Bootstrapped.Fail);
+      });
   }, []);
 
+  // Collections are only downloaded once they're searched, or once a
+  // reference points into one that isn't. Searching again as each one
+  // arrives fills in the results that were pending on it.
+  const includeSource = preferences.toSearch.includeSource;
+  React.useEffect(() => {
+    COLLECTIONS
+      .filter(collection => includeSource[collection] || pending.indexOf(collection) !== -1)
+      .forEach(collection => loadCollection(collection, collectionProgress => {
+        setProgress(current => ({ ...current, [collection]: collectionProgress }));
+        if (collectionProgress.done) {
+          setLoadedCount(count => count + 1);
+        }
+      }).catch(e => console.error(e)));
+  }, [includeSource, pending]);
+
   React.useEffect(() => {
     if (bootstrapped !== Bootstrapped.Y) {
       return;
@@ -156,7 +205,7 @@ function App({}: AppProps) {
       searchTerm,
       preferences,
     );
-  }, [bootstrapped, searchTerm, preferences]);
+  }, [bootstrapped, searchTerm, preferences, loadedCount]);
 
   const boundSetSearchTerm = React.useCallback(
     newTerm => {
@@ -182,11 +231,23 @@ function App({}: AppProps) {
           setPreferences={setPreferences}
           resultCount={resultCount}
         />
+        <Suggestions
+          searchTerm={searchTerm}
+          setSearchTerm={boundSetSearchTerm}
+          suggestions={suggestions}
+        />
+        <Pending
+          pending={pending}
+          progress={progress}
+        />
+        {invalidReference && <div>
+          There's no {invalidReference} in the scriptures.
+        </div>}
         <ul id="scriptured-results" className="results-section" onClick={previewListener as any} />
       </div>
     case Bootstrapped.Fail:
       return <div className="fail">
-        Failed to bootstrap searcher.
+        Failed to bootstrap searcher: {failure}
       </div>
   }
 }
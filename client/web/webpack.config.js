@@ -3,6 +3,16 @@ const CopyWebpackPlugin = require("copy-webpack-plugin");
 
 const path = require('path');
 
+// Each collection is fetched separately, once it's searched. The client
+// gunzips them itself.
+const COLLECTION_FILES = [
+  'old-testament.json.bin.gz',
+  'new-testament.json.bin.gz',
+  'book-of-mormon.json.bin.gz',
+  'doctrine-and-covenants.json.bin.gz',
+  'pearl-of-great-price.json.bin.gz',
+];
+
 module.exports = {
   entry: "./bootstrap.js",
   output: {
@@ -11,7 +21,11 @@ module.exports = {
   },
   mode: process.env.NODE_ENV === 'production' ? 'production' : 'development',
   plugins: [
-    new CopyWebpackPlugin(['./src/_headers']),
+    new CopyWebpackPlugin([
+      './src/_headers',
+      { from: '../../data-bundler/data/index.bin.gz', to: 'index.bin.gz' },
+      ...COLLECTION_FILES.map(file => ({ from: `../../data-bundler/data/${file}`, to: file })),
+    ]),
     new HtmlWebpackPlugin({
       filename: './index.html',
       template: './src/index.html'
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use indices::IndexError;
+
+use crate::artifact::ArtifactError;
+
+#[derive(Debug)]
+pub enum BundleError {
+    // A file couldn't be read or written.
+    Io { path: PathBuf, error: io::Error },
+    MissingSourceFiles { source: String, files: Vec<&'static str> },
+    // npm couldn't be run, or failed.
+    Install(io::Error),
+    MalformedJson { path: PathBuf, line: usize, column: usize, error: serde_json::Error },
+    Serialization { path: PathBuf, error: bincode::Error },
+    // More passages than fit in a u16 scripture id.
+    IndexOverflow { passages: usize },
+    Index(IndexError),
+    Artifact { path: PathBuf, error: ArtifactError },
+    // The intermediate indices in a folder came from different runs.
+    MixedIndices { dest: PathBuf },
+    // An index built from another release of the scriptures than the one
+    // it's being checked against.
+    CorpusMismatch { path: PathBuf, corpus: String },
+}
+
+impl BundleError {
+    pub fn io(path: PathBuf) -> impl FnOnce(io::Error) -> BundleError {
+        move |error| BundleError::Io { path, error }
+    }
+
+    pub fn malformed_json(path: PathBuf) -> impl FnOnce(serde_json::Error) -> BundleError {
+        move |error| BundleError::MalformedJson { path, line: error.line(), column: error.column(), error }
+    }
+
+    pub fn serialization(path: PathBuf) -> impl FnOnce(bincode::Error) -> BundleError {
+        move |error| BundleError::Serialization { path, error }
+    }
+
+    pub fn artifact(path: PathBuf) -> impl FnOnce(ArtifactError) -> BundleError {
+        move |error| BundleError::Artifact { path, error }
+    }
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BundleError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            BundleError::MissingSourceFiles { source, files } => {
+                write!(f, "{} is missing {}; pass --install to get them with npm", source, files.join(", "))
+            },
+            BundleError::Install(error) => write!(f, "can't install scriptures-json with npm: {}", error),
+            BundleError::MalformedJson { path, line, column, error } => {
+                write!(f, "{}:{}:{}: malformed scriptures: {}", path.display(), line, column, error)
+            },
+            BundleError::Serialization { path, error } => write!(f, "{}: can't serialize: {}", path.display(), error),
+            BundleError::IndexOverflow { passages } => write!(
+                f,
+                "{} passages don't fit in scripture ids, which stop at {}",
+                passages,
+                u16::MAX,
+            ),
+            BundleError::Index(error) => write!(f, "can't build index: {}", error),
+            BundleError::Artifact { path, error } => write!(f, "{}: {}", path.display(), error),
+            BundleError::MixedIndices { dest } => {
+                write!(f, "the indices in {} were built from different releases; rerun the index stage", dest.display())
+            },
+            BundleError::CorpusMismatch { path, corpus } => {
+                write!(f, "{} was built from another release of the scriptures:\n{}", path.display(), corpus)
+            },
+        }
+    }
+}
+
+impl From<IndexError> for BundleError {
+    fn from(error: IndexError) -> Self {
+        BundleError::Index(error)
+    }
+}
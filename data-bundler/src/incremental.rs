@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::BundleError;
+use crate::manifest::Manifest;
+
+pub const INPUTS_FILE: &str = "inputs.json";
+
+// Hashes a stage's inputs together. Each one is prefixed with its length, so
+// that moving bytes from one input to the next changes the hash.
+pub fn hash_inputs(inputs: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for input in inputs {
+        hasher.update((input.len() as u64).to_le_bytes());
+        hasher.update(input);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+// The inputs each stage last ran with, so that a stage can be skipped when
+// they haven't changed. Kept apart from the manifest, since the inputs
+// include the bundler's own executable, which differs from machine to
+// machine.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct StageInputs {
+    pub stages: BTreeMap<String, String>,
+}
+
+impl StageInputs {
+    // Nothing is recorded if the folder has no inputs file yet.
+    pub fn read(dest_folder: &Path) -> Result<StageInputs, BundleError> {
+        let path = dest_folder.join(INPUTS_FILE);
+        if !path.exists() {
+            return Ok(StageInputs::default());
+        }
+        let unparsed = std::fs::read_to_string(&path).map_err(BundleError::io(path.clone()))?;
+        serde_json::from_str(&unparsed).map_err(BundleError::malformed_json(path))
+    }
+
+    pub fn write(&self, dest_folder: &Path) -> Result<(), BundleError> {
+        let path = dest_folder.join(INPUTS_FILE);
+        let mut json = serde_json::to_string_pretty(self).unwrap();
+        json.push('\n');
+        std::fs::write(&path, json).map_err(BundleError::io(path))
+    }
+
+    // Whether a stage can be skipped: it last ran with the same inputs, and
+    // everything it wrote is still there, as the manifest recorded it.
+    pub fn is_fresh(&self, stage: &str, inputs: &str, outputs: &[String], manifest: &Manifest, dest_folder: &Path) -> bool {
+        self.stages.get(stage).map(|last| last == inputs).unwrap_or(false)
+            && outputs.iter().all(|file_name| manifest.is_intact(dest_folder, file_name))
+    }
+
+    pub fn record(&mut self, stage: &str, inputs: &str) {
+        self.stages.insert(stage.to_string(), inputs.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separates_inputs() {
+        assert_ne!(hash_inputs(&[b"ab", b"c"]), hash_inputs(&[b"a", b"bc"]));
+        assert_eq!(hash_inputs(&[b"ab", b"c"]), hash_inputs(&[b"ab", b"c"]));
+    }
+
+    #[test]
+    fn is_fresh_until_inputs_or_outputs_change() {
+        let dest_folder = std::env::temp_dir().join(format!("data-bundler-inputs-{}", std::process::id()));
+        std::fs::create_dir_all(&dest_folder).unwrap();
+        std::fs::write(dest_folder.join("index.bin"), b"index").unwrap();
+        let mut manifest = Manifest::default();
+        manifest.record("index.bin", b"index");
+        let outputs = vec![String::from("index.bin")];
+
+        let mut stage_inputs = StageInputs::default();
+        assert!(!stage_inputs.is_fresh("codegen", "inputs", &outputs, &manifest, &dest_folder));
+        stage_inputs.record("codegen", "inputs");
+        stage_inputs.write(&dest_folder).unwrap();
+
+        let stage_inputs = StageInputs::read(&dest_folder).unwrap();
+        assert!(stage_inputs.is_fresh("codegen", "inputs", &outputs, &manifest, &dest_folder));
+        assert!(!stage_inputs.is_fresh("codegen", "other inputs", &outputs, &manifest, &dest_folder));
+        assert!(!stage_inputs.is_fresh("index", "inputs", &outputs, &manifest, &dest_folder));
+
+        std::fs::write(dest_folder.join("index.bin"), b"tampered").unwrap();
+        assert!(!stage_inputs.is_fresh("codegen", "inputs", &outputs, &manifest, &dest_folder));
+
+        std::fs::remove_dir_all(&dest_folder).unwrap();
+    }
+}
@@ -2,7 +2,16 @@ extern crate scripture_types;
 extern crate rust_stemmers;
 use rust_stemmers::{Algorithm, Stemmer};
 use fnv::FnvHashMap;
+use rayon::prelude::*;
 use std::collections::hash_map::Entry;
+
+pub mod error;
+pub mod incremental;
+pub mod manifest;
+pub mod source;
+pub mod verify;
+pub use indices::{artifact, compression, decode_highlights, encode_highlights, get_word_ranges, PostingError};
+use error::BundleError;
 use scripture_types::{
     OldTestament,
     NewTestament,
@@ -10,9 +19,18 @@ use scripture_types::{
     DoctrineAndCovenants,
     PearlOfGreatPrice,
     Verse,
+    Book,
     Chapter,
     WordsIndex,
+    SurfaceIndex,
+    StemFormsIndex,
+    SynonymsIndex,
     PathsIndex,
+    HeadingPathsIndex,
+    HeadingPath,
+    HeadingField,
+    FrontMatterPathsIndex,
+    FrontMatterPath,
     VersePath,
 };
 
@@ -22,7 +40,6 @@ pub enum HasBooks<'a> {
     BOM(&'a BookOfMormon),
     POGP(&'a PearlOfGreatPrice),
 }
-use primitive_types::U256;
 
 fn prepare_book_paths<'a>(coll: HasBooks<'a>) -> Vec<(u8, u8, &'a Verse)> {
     let (books, title) = match coll {
@@ -59,136 +76,282 @@ fn prepare_book_paths<'a>(coll: HasBooks<'a>) -> Vec<(u8, u8, &'a Verse)> {
     with_chapters
 }
 
-fn get_word_ranges(text: &String) -> Vec<(usize, usize)> {
-    let mut results: Vec<(usize, usize)> = vec![];
-    let mut open: Option<usize> = None;
-    text.char_indices().for_each(|(idx, letter): (usize, char)| {
-        let is_word_char = letter.is_alphanumeric() || letter == 'æ' || letter == '-';
-        if is_word_char {
-            if open == None {
-                open = Some(idx);
-            }
-        } else {
-            if let Some(open_idx) = open {
-                results.push((open_idx, idx));
-                open = None;
-            }
+fn push_heading<'a>(
+    headings: &mut Vec<(HeadingPath, &'a String)>,
+    verse_path: VersePath,
+    field: HeadingField,
+    text: &'a Option<String>,
+) {
+    if let Some(text) = text {
+        if !text.is_empty() {
+            headings.push((HeadingPath { verse_path, field }, text));
         }
-    });
+    }
+}
 
-    results
+fn push_verse_headings<'a>(headings: &mut Vec<(HeadingPath, &'a String)>, verse_path: VersePath, verse: &'a Verse) {
+    push_heading(headings, verse_path.clone(), HeadingField::VerseHeading, &verse.heading);
+    push_heading(headings, verse_path, HeadingField::VerseSubheading, &verse.subheading);
 }
 
-// unsafe
-// each highlight length (u8) must be no bigger than 5 bits
-pub fn pack_lengths(lengths: &Vec<u8>) -> u128 {
-    let mut result: u128 = 0;
-    for i in lengths {
-        if result > 0 {
-            result = result << 5;
+// Headings and notes in scripture order, with a book's coming before its
+// chapters' and a chapter's before its verses'.
+fn prepare_book_headings(
+    books: &[Book],
+    make_path: fn(u8, u8, u16) -> VersePath,
+) -> Vec<(HeadingPath, &String)> {
+    let mut headings = vec![];
+    for (book_num, book) in books.iter().enumerate() {
+        let book_num = book_num as u8;
+        push_heading(&mut headings, make_path(book_num, 0, 0), HeadingField::BookHeading, &book.heading);
+        push_heading(&mut headings, make_path(book_num, 0, 0), HeadingField::BookNote, &book.note);
+        for chapter in &book.chapters {
+            let chapter_num = chapter.chapter - 1;
+            push_heading(&mut headings, make_path(book_num, chapter_num, 0), HeadingField::ChapterHeading, &chapter.heading);
+            push_heading(&mut headings, make_path(book_num, chapter_num, 0), HeadingField::ChapterNote, &chapter.note);
+            for verse in &chapter.verses {
+                push_verse_headings(&mut headings, make_path(book_num, chapter_num, verse.verse - 1), verse);
+            }
         }
-        result = result + *i as u128
     }
-    result
+    headings
 }
 
-pub fn unpack_lengths(packed: u128) -> Vec<u8> {
-    let mut tmp = packed.clone();
-    let mut result: Vec<u8> = vec![];
-
-    while tmp > 0 {
-        let diff = (tmp >> 5) << 5;
-        result.push((tmp - diff) as u8);
-        tmp = diff >> 5;
+fn prepare_section_headings(dc: &DoctrineAndCovenants) -> Vec<(HeadingPath, &String)> {
+    let mut headings = vec![];
+    for section in &dc.sections {
+        for verse in &section.verses {
+            push_verse_headings(&mut headings, VersePath::PathDC(section.section - 1, verse.verse - 1), verse);
+        }
     }
-    result.iter().map(|x| *x).rev().collect()
+    headings
 }
 
-// unsafe
-// u16s must be no bigger than 11 bits - 1 (need each chunk to have a min value of 1) for efficient
-// unpacking
-pub fn pack_indices(indices: &Vec<u16>) -> U256 {
-    // offset all by one.
-    let mut result: U256 = U256::from(0);
-    for i in indices {
-        if !result.is_zero() {
-            result = result << 11;
+// Title pages, testimonies, facsimile explanations and section signatures,
+// in scripture order.
+fn prepare_front_matter<'a>(
+    nt: &'a NewTestament,
+    bom: &'a BookOfMormon,
+    dc: &'a DoctrineAndCovenants,
+    pogp: &'a PearlOfGreatPrice,
+) -> Vec<(FrontMatterPath, &'a String)> {
+    let mut front_matter = vec![];
+    front_matter.push((FrontMatterPath::NTTitlePage, &nt.title_page.text));
+    for (paragraph_num, paragraph) in bom.title_page.text.iter().enumerate() {
+        front_matter.push((FrontMatterPath::BoMTitlePage(paragraph_num as u8), paragraph));
+    }
+    for (testimony_num, testimony) in bom.testimonies.iter().enumerate() {
+        front_matter.push((FrontMatterPath::BoMTestimony(testimony_num as u8), &testimony.text));
+    }
+    for section in &dc.sections {
+        if let Some(signature) = &section.signature {
+            front_matter.push((FrontMatterPath::DCSignature(section.section - 1), signature));
         }
-        result = result + U256::from(*i + 1);
     }
-    result
-}
-
-pub fn pack_indices_arr(indices: &Vec<u16>) -> [u64;4] {
-    // offset all by one.
-    let mut num: U256 = U256::from(0);
-    for i in indices {
-        if !num.is_zero() {
-            num = num << 11;
+    for (book_num, book) in pogp.books.iter().enumerate() {
+        for (facsimile_num, facsimile) in book.facsimiles.iter().flatten().enumerate() {
+            for (explanation_num, explanation) in facsimile.explanations.iter().enumerate() {
+                front_matter.push((
+                    FrontMatterPath::FacsimileExplanation(book_num as u8, facsimile_num as u8, explanation_num as u8),
+                    explanation,
+                ));
+            }
         }
-        num = num + U256::from(*i + 1);
     }
+    front_matter
+        .into_iter()
+        .filter(|(_, text)| !text.is_empty())
+        .collect()
+}
 
-    let mut num_arr: [u64;4] = [0,0,0,0];
+// The index key for a word as it appears in the text.
+pub fn stem_word(stemmer: &Stemmer, word: &str) -> String {
+    stemmer.stem(&word.to_lowercase()).to_string()
+}
 
-    let new_num = num >> 64 << 64;
-    num_arr[0] = (num - new_num).as_u64();
-    num = new_num;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let new_num = num >> 64 << 64;
-    num_arr[1] = (num - new_num).as_u64();
-    num = new_num;
+    #[test]
+    fn averages_verse_length_over_all_verses() {
+        let mut words_index: WordsIndex = FnvHashMap::default();
+        insert_usage(&mut words_index, String::from("faith"), 1, (0, 5));
+        insert_usage(&mut words_index, String::from("faith"), 2, (0, 5));
+        insert_usage(&mut words_index, String::from("faith"), 2, (10, 5));
+        insert_usage(&mut words_index, String::from("hope"), 3, (0, 4));
+        assert_eq!(2.0, average_verse_length(&words_index, 2));
+        assert_eq!(0.0, average_verse_length(&words_index, 0));
+    }
 
-    let new_num = num >> 64 << 64;
-    num_arr[2] = (num - new_num).as_u64();
-    num = new_num;
+    #[test]
+    fn builds_symmetric_synonyms_by_stem() {
+        let synonyms = build_synonyms("# comment\n\nhath, has\nsaith , Says\nye, you, thee\nyou, thou\n");
+        assert_eq!(Some(&vec![String::from("has")]), synonyms.get("hath"));
+        assert_eq!(Some(&vec![String::from("hath")]), synonyms.get("has"));
+        assert_eq!(Some(&vec![String::from("say")]), synonyms.get("saith"));
+        assert_eq!(
+            Some(&vec![String::from("thee"), String::from("thou"), String::from("ye")]),
+            synonyms.get("you"),
+        );
+        assert_eq!(None, synonyms.get("comment"));
+    }
 
-    let new_num = num >> 64 << 64;
-    num_arr[3] = (num - new_num).as_u64();
+    fn book(name: &str, verses: &[&str]) -> serde_json::Value {
+        let verses: Vec<serde_json::Value> = verses
+            .iter()
+            .enumerate()
+            .map(|(i, text)| serde_json::json!({ "reference": format!("{} 1:{}", name, i + 1), "text": text, "verse": i + 1 }))
+            .collect();
+        serde_json::json!({
+            "book": name,
+            "chapters": [{ "chapter": 1, "heading": format!("The {} heading", name), "reference": format!("{} 1", name), "verses": verses }],
+            "full_title": name,
+            "lds_slug": name,
+        })
+    }
 
-    num_arr
+    #[test]
+    fn numbers_passages_in_canonical_order() {
+        let release = serde_json::json!({ "last_modified": "2019-11-08", "lds_slug": "", "title": "", "subtitle": "", "version": 1 });
+        let collection = |extra: serde_json::Value| {
+            let mut collection = release.clone();
+            collection.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            collection
+        };
+        let ot: OldTestament = serde_json::from_value(collection(serde_json::json!({
+            "books": [book("Genesis", &["In the beginning", "And God said"])],
+            "the_end": "",
+        }))).unwrap();
+        let nt: NewTestament = serde_json::from_value(collection(serde_json::json!({
+            "books": [book("Matthew", &["Blessed are the meek."])],
+            "title_page": { "subtitle": "", "text": "", "title": "" },
+        }))).unwrap();
+        let bom: BookOfMormon = serde_json::from_value(collection(serde_json::json!({
+            "books": [book("Nephi", &["I, Nephi, having been born of goodly parents"])],
+            "testimonies": [],
+            "title_page": { "subtitle": "", "text": [], "title": "", "translated_by": "" },
+        }))).unwrap();
+        let dc: DoctrineAndCovenants = serde_json::from_value(collection(serde_json::json!({
+            "sections": [{ "section": 1, "reference": "D&C 1", "verses": [
+                { "reference": "D&C 1:1", "text": "Hearken, O ye people", "verse": 1 },
+            ] }],
+            "subsubtitle": "",
+        }))).unwrap();
+        let pogp: PearlOfGreatPrice = serde_json::from_value(collection(serde_json::json!({
+            "books": [book("Moses", &["The words of God."])],
+        }))).unwrap();
+
+        let (words_index, surface_index, paths_index, heading_paths_index, _) =
+            build_index(&ot, &nt, &bom, &dc, &pogp).unwrap();
+
+        assert_eq!(Some(&VersePath::PathOT(0, 0, 1)), paths_index.get(&2));
+        assert_eq!(Some(&VersePath::PathNT(0, 0, 0)), paths_index.get(&3));
+        assert_eq!(Some(&VersePath::PathDC(0, 0)), paths_index.get(&5));
+        assert_eq!(Some(&VersePath::PathPOGP(0, 0, 0)), paths_index.get(&6));
+        assert_eq!(Some(&HeadingField::ChapterHeading), heading_paths_index.get(&7).map(|path| &path.field));
+        assert_eq!(Some(&vec![(4, 3)]), words_index["god"].get(&2));
+        assert_eq!(Some(&vec![(13, 3)]), words_index["god"].get(&6));
+        assert_eq!(vec![&3], surface_index["meek"].keys().collect::<Vec<&u16>>());
+    }
 }
 
-pub fn unpack_indices(packed: U256) -> Vec<u16> {
-    // offset all by one.
-    let mut tmp = packed.clone();
-    let mut result: Vec<u16> = vec![];
+fn insert_usage(index: &mut WordsIndex, key: String, scripture_id: u16, usage: (usize, usize)) {
+    match index.entry(key) {
+        Entry::Vacant(vacant) => {
+            let mut verses_using_word = FnvHashMap::default();
+            verses_using_word.insert(scripture_id, vec![usage]);
 
-    while !tmp.is_zero() {
-        let diff = (tmp >> 11) << 11;
-        result.push(((tmp - diff) - 1).as_u32() as u16);
-        tmp = diff >> 11;
-    }
-    result.iter().map(|x| *x).rev().collect()
+            vacant.insert(verses_using_word);
+        },
+        Entry::Occupied(mut verses_using_word) => {
+            let verses_using_word_val = verses_using_word.get_mut();
+            let verse_usage_entry = verses_using_word_val.entry(scripture_id);
+            verse_usage_entry
+                .and_modify(|verse_usage| { verse_usage.push(usage); })
+                .or_insert(vec![usage]);
+        },
+    };
 }
 
+// Maps each stem to every cased surface form that reduces to it.
+pub fn build_stem_forms(surface_index: &SurfaceIndex) -> StemFormsIndex {
+    let en_stemmer = Stemmer::create(Algorithm::English);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn test_round_trip(lenghts: Vec<u8>) {
-        assert_eq!(lenghts, unpack_lengths(pack_lengths(&lenghts)));
+    let mut stem_forms: StemFormsIndex = FnvHashMap::default();
+    for form in surface_index.keys() {
+        let stemmed = en_stemmer.stem(&form.to_lowercase()).to_string();
+        stem_forms.entry(stemmed).or_default().push(form.to_string());
     }
-
-    fn test_round_trip_indices(indices: Vec<u16>) {
-        assert_eq!(indices, unpack_indices(pack_indices(&indices)));
+    for forms in stem_forms.values_mut() {
+        forms.sort();
     }
 
-    #[test]
-    fn packs_and_unpacks_highlight_lengths_less_than_32_up_to_22_elements() {
-        test_round_trip(vec![]);
-        test_round_trip((1..22).collect());
-        test_round_trip((10..31).collect());
+    stem_forms
+}
+
+// Mean number of indexed words per verse, for length normalization when
+// ranking results.
+pub fn average_verse_length(words_index: &WordsIndex, verse_count: usize) -> f64 {
+    if verse_count == 0 {
+        return 0.0;
     }
+    let total_words: usize = words_index
+        .values()
+        .flat_map(|usages| usages.values())
+        .map(|highlights| highlights.len())
+        .sum();
+    total_words as f64 / verse_count as f64
+}
 
-    #[test]
-    fn packs_and_unpacks_highlight_indices_less_than_2048_up_to_22_elements() {
-        test_round_trip_indices(vec![]);
-        test_round_trip_indices(vec![1234,2046,0,1,2,3,4,5,88]);
-        test_round_trip_indices((0..21).collect());
-        test_round_trip_indices((2025..2046).collect());
+// Reads a table of equivalent words, one comma separated group per line, and
+// maps each stem to the stems of the other words in its group. Blank lines
+// and lines starting with `#` are skipped.
+pub fn build_synonyms(table: &str) -> SynonymsIndex {
+    let en_stemmer = Stemmer::create(Algorithm::English);
+
+    let mut synonyms: SynonymsIndex = FnvHashMap::default();
+    for line in table.lines().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut stems: Vec<String> = line
+            .split(',')
+            .map(|word| word.trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .map(|word| en_stemmer.stem(&word).to_string())
+            .collect();
+        stems.sort();
+        stems.dedup();
+
+        for stem in &stems {
+            let equivalents = synonyms.entry(stem.to_string()).or_default();
+            for other in &stems {
+                if other != stem && !equivalents.contains(other) {
+                    equivalents.push(other.to_string());
+                }
+            }
+        }
+    }
+    for equivalents in synonyms.values_mut() {
+        equivalents.sort();
     }
+
+    synonyms
+}
+
+// How many scripture ids `build_index` hands out: one for each verse,
+// heading, note and piece of front matter.
+fn passage_count(
+    book_paths: &[&Vec<(u8, u8, &Verse)>],
+    dc: &DoctrineAndCovenants,
+    headings: usize,
+    front_matter: usize,
+) -> usize {
+    let verses = book_paths.iter().map(|paths| paths.len()).sum::<usize>()
+        + dc.sections.iter().map(|section| section.verses.len()).sum::<usize>();
+    verses + headings + front_matter
 }
 
 pub fn build_index(
@@ -197,157 +360,115 @@ pub fn build_index(
     bom: &BookOfMormon,
     dc: &DoctrineAndCovenants,
     pogp: &PearlOfGreatPrice,
-) -> (WordsIndex, PathsIndex) {
-    let mut scripture_id: u16 = 0;
-
-    let en_stemmer = Stemmer::create(Algorithm::English);
-
-    let indices: (WordsIndex, PathsIndex) =
-        (FnvHashMap::default(), FnvHashMap::default());
-
-    let count_word_usage = |mut words_index: WordsIndex, verse: &String, (i_from, i_to): &(usize, usize), scripture_id: u16| {
-        let f = *i_from;
-        let t = *i_to;
-
-        let i = *i_from;
-        let l = t - i;
-
-        let word_slice = &verse[f..t].to_lowercase();
-        let stemmed = en_stemmer.stem(word_slice).to_string();
-        let to_insert: Vec<(usize, usize)> = vec![(i, l)];
-
-        match words_index.entry(stemmed) {
-            Entry::Vacant(vacant) => {
-                let mut verses_using_word = FnvHashMap::default();
-                verses_using_word.insert(scripture_id, to_insert);
-
-                vacant.insert(verses_using_word);
-            },
-            Entry::Occupied(mut verses_using_word) => {
-                let verses_using_word_val = verses_using_word.get_mut();
-                let verse_usage_entry = verses_using_word_val.entry(scripture_id);
-                verse_usage_entry
-                    .and_modify(|verse_usage| { verse_usage.push((i,l)); })
-                    .or_insert(to_insert);
-            },
-        };
-
-        words_index
-    };
-
-    let count_verse = |verse_text: &String, words_index: WordsIndex, id| {
-        let index_with_verse_added = get_word_ranges(verse_text)
-            .iter()
-            .fold(words_index, |acc, word_indices| count_word_usage(acc, verse_text, word_indices, id));
-        index_with_verse_added
-    };
-
-    // Old Testament
-    let indices = prepare_book_paths(HasBooks::OT(ot)).iter().fold(
-        indices,
-        |(words_indices, mut path_index), (book_num, chapter_num, verse)| {
-            scripture_id += 1;
-            path_index.insert(
-                scripture_id,
-                VersePath::PathOT(
-                    *book_num,
-                    *chapter_num,
-                    verse.verse - 1,
-                ),
-            );
-            (
-                count_verse(&verse.text, words_indices, scripture_id),
-                path_index,
-            )
-        },
-    );
-
-    // New Testament
-    let indices = prepare_book_paths(HasBooks::NT(nt)).iter().fold(
-        indices,
-        |(words_indices, mut path_index), (book_num, chapter_num, verse)| {
-            scripture_id += 1;
-            path_index.insert(
-                scripture_id,
-                VersePath::PathNT(
-                    *book_num,
-                    *chapter_num,
-                    verse.verse - 1,
-                ),
-            );
-            (
-                count_verse(&verse.text, words_indices, scripture_id),
-                path_index,
-            )
-        },
+) -> Result<(WordsIndex, SurfaceIndex, PathsIndex, HeadingPathsIndex, FrontMatterPathsIndex), BundleError> {
+    let ot_paths = prepare_book_paths(HasBooks::OT(ot));
+    let nt_paths = prepare_book_paths(HasBooks::NT(nt));
+    let bom_paths = prepare_book_paths(HasBooks::BOM(bom));
+    let pogp_paths = prepare_book_paths(HasBooks::POGP(pogp));
+    let headings: Vec<(HeadingPath, &String)> = prepare_book_headings(&ot.books, VersePath::PathOT)
+        .into_iter()
+        .chain(prepare_book_headings(&nt.books, VersePath::PathNT))
+        .chain(prepare_book_headings(&bom.books, VersePath::PathBoM))
+        .chain(prepare_section_headings(dc))
+        .chain(prepare_book_headings(&pogp.books, VersePath::PathPOGP))
+        .collect();
+    let front_matter = prepare_front_matter(nt, bom, dc, pogp);
+
+    // Ids start at 1, so the last one is the passage count.
+    let passages = passage_count(
+        &[&ot_paths, &nt_paths, &bom_paths, &pogp_paths],
+        dc,
+        headings.len(),
+        front_matter.len(),
     );
+    if passages > u16::MAX as usize {
+        return Err(BundleError::IndexOverflow { passages });
+    }
 
-    // Book of Mormon
-    let indices = prepare_book_paths(HasBooks::BOM(bom)).iter().fold(
-        indices,
-        |(words_indices, mut path_index), (book_num, chapter_num, verse)| {
-            scripture_id += 1;
-            path_index.insert(
-                scripture_id,
-                VersePath::PathBoM(
-                    *book_num,
-                    *chapter_num,
-                    verse.verse - 1,
-                ),
-            );
-            (
-                count_verse(&verse.text, words_indices, scripture_id),
-                path_index,
-            )
-        },
-    );
+    // Ids are handed out up front, in canonical order, so that they don't
+    // depend on how the tokenizing below is split between threads.
+    let mut texts: Vec<(u16, &String)> = Vec::with_capacity(passages);
+
+    let verses = ot_paths
+        .into_iter()
+        .map(|(book_num, chapter_num, verse)| (VersePath::PathOT(book_num, chapter_num, verse.verse - 1), verse))
+        .chain(
+            nt_paths
+                .into_iter()
+                .map(|(book_num, chapter_num, verse)| (VersePath::PathNT(book_num, chapter_num, verse.verse - 1), verse)),
+        )
+        .chain(
+            bom_paths
+                .into_iter()
+                .map(|(book_num, chapter_num, verse)| (VersePath::PathBoM(book_num, chapter_num, verse.verse - 1), verse)),
+        )
+        .chain(dc.sections.iter().flat_map(|section| {
+            section.verses.iter().map(move |verse| (VersePath::PathDC(section.section - 1, verse.verse - 1), verse))
+        }))
+        .chain(
+            pogp_paths
+                .into_iter()
+                .map(|(book_num, chapter_num, verse)| (VersePath::PathPOGP(book_num, chapter_num, verse.verse - 1), verse)),
+        );
+    let mut paths_index: PathsIndex = FnvHashMap::default();
+    for (verse_path, verse) in verses {
+        let scripture_id = texts.len() as u16 + 1;
+        paths_index.insert(scripture_id, verse_path);
+        texts.push((scripture_id, &verse.text));
+    }
 
-    // Doctrine and Covenants
-    println!("    {}", dc.title);
-    let with_section_nums: Vec<(usize, &Verse)> = (dc)
-        .sections
-        .iter()
-        .flat_map(|section| {
-            let with_section_nums: Vec<(usize, &Verse)> = section
-                .verses
-                .iter()
-                .map(|v| (section.section as usize - 1, v))
-                .collect();
+    // Headings and notes are numbered after every verse, so verse ids stay
+    // the same whether or not they are searched.
+    let mut heading_paths_index: HeadingPathsIndex = FnvHashMap::default();
+    for (heading_path, text) in headings {
+        let scripture_id = texts.len() as u16 + 1;
+        heading_paths_index.insert(scripture_id, heading_path);
+        texts.push((scripture_id, text));
+    }
 
-            with_section_nums
-        })
-        .collect();
+    let mut front_matter_paths_index: FrontMatterPathsIndex = FnvHashMap::default();
+    for (front_matter_path, text) in front_matter {
+        let scripture_id = texts.len() as u16 + 1;
+        front_matter_paths_index.insert(scripture_id, front_matter_path);
+        texts.push((scripture_id, text));
+    }
 
-    let indices = with_section_nums.iter().fold(
-        indices,
-        |(words_indices, mut path_index), (section_num, verse)| {
-            scripture_id += 1;
-            path_index.insert(
-                scripture_id,
-                VersePath::PathDC(*section_num as u8, verse.verse - 1),
-            );
-            (
-                count_verse(&verse.text, words_indices, scripture_id),
-                path_index,
-            )
-        },
-    );
+    println!("    Tokenizing {} passages on {} threads", texts.len(), rayon::current_num_threads());
+    let en_stemmer = Stemmer::create(Algorithm::English);
+    let empty = || (FnvHashMap::default(), FnvHashMap::default());
+    let (words_index, surface_index) = texts
+        .par_iter()
+        .fold(empty, |indices, (scripture_id, text)| count_passage(&en_stemmer, indices, text, *scripture_id))
+        .reduce(empty, |(words_a, surface_a), (words_b, surface_b)| {
+            (merge_index(words_a, words_b), merge_index(surface_a, surface_b))
+        });
+
+    Ok((words_index, surface_index, paths_index, heading_paths_index, front_matter_paths_index))
+}
 
-    // Pearl of Great Price
-    let indices = prepare_book_paths(HasBooks::POGP(pogp)).iter().fold(
-        indices,
-        |(words_indices, mut path_index), (book_num, chapter_num, verse)| {
-            scripture_id += 1;
-            path_index.insert(
-                scripture_id,
-                VersePath::PathPOGP(*book_num, *chapter_num, verse.verse - 1),
-            );
-            (
-                count_verse(&verse.text, words_indices, scripture_id),
-                path_index,
-            )
-        },
-    );
+// Adds the usages of each word in a passage.
+fn count_passage(
+    stemmer: &Stemmer,
+    (mut words_index, mut surface_index): (WordsIndex, SurfaceIndex),
+    text: &str,
+    scripture_id: u16,
+) -> (WordsIndex, SurfaceIndex) {
+    for (from, to) in get_word_ranges(text) {
+        let word_slice = &text[from..to];
+        insert_usage(&mut words_index, stem_word(stemmer, word_slice), scripture_id, (from, to - from));
+        // Surface forms keep their original casing so that searches can
+        // tell LORD, Lord and lord apart.
+        insert_usage(&mut surface_index, word_slice.to_string(), scripture_id, (from, to - from));
+    }
+    (words_index, surface_index)
+}
 
-    (indices.0, indices.1)
+// Merges two partial indices. Each passage is only tokenized into one of
+// them, so their usages never collide.
+fn merge_index(into: WordsIndex, from: WordsIndex) -> WordsIndex {
+    let (mut into, from) = if into.len() < from.len() { (from, into) } else { (into, from) };
+    for (word, usages) in from {
+        into.entry(word).or_default().extend(usages);
+    }
+    into
 }
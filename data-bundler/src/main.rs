@@ -1,207 +1,507 @@
 extern crate scripture_types;
-extern crate phf_codegen;
-extern crate phf;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-use data_bundler;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use data_bundler::artifact::collection_version;
+use data_bundler::error::BundleError;
+use data_bundler::incremental::{hash_inputs, StageInputs};
+use data_bundler::manifest::{sha256, Manifest, MANIFEST_FILE};
+use data_bundler::source::{DataSource, SOURCE_FILES};
+use scripture_types::{
+    BookOfMormon, DoctrineAndCovenants, FrontMatterPathsIndex, HeadingPathsIndex, NewTestament, OldTestament,
+    PathsIndex, PearlOfGreatPrice, SurfaceIndex, Versioned, WordsIndex,
+};
 
 #[cfg(windows)]
-pub const NPM: &'static str = "npm.cmd";
+pub const NPM: &str = "npm.cmd";
 
 #[cfg(not(windows))]
-pub const NPM: &'static str = "npm";
+pub const NPM: &str = "npm";
+
+static SYNONYMS_TABLE: &str = include_str!("../synonyms.txt");
 // enum AndOr {
 //     And = 1,
 //     Or = 0,
 // }
 
-fn ensure_data_source(test_path: &std::path::PathBuf) {
-    if Path::new(&test_path).exists() {
-        println!("\nData source exists, skipping install.");
-    } else {
+// Opens the scriptures-json files at `src`. npm is only run when asked to,
+// so that bundling works offline and uses exactly the files it's given.
+fn open_source(src: &Path, install: bool) -> Result<DataSource, BundleError> {
+    let incomplete = |source: &std::io::Result<DataSource>| match source {
+        Ok(source) => !source.missing_files().is_empty(),
+        Err(_) => true,
+    };
+    let mut source = DataSource::open(src);
+    if install && incomplete(&source) {
         println!("\nData source not found, installing...");
-        std::process::Command::new("sh")
+        let status = std::process::Command::new("sh")
             .arg("-c")
             .arg(format!("{} i", NPM))
             .status()
-            .expect("Unable to install data source");
+            .map_err(BundleError::Install)?;
+        if !status.success() {
+            let error = std::io::Error::other(format!("{} i exited with {}", NPM, status));
+            return Err(BundleError::Install(error));
+        }
+        source = DataSource::open(src);
     }
-}
 
-fn repr_verse_path(verse_path: &scripture_types::VersePath) -> String {
-    match verse_path {
-        scripture_types::VersePath::PathBoM(a, b, c) => format!("VersePath::PathBoM({},{},{})", a, b, c),
-        scripture_types::VersePath::PathOT(a, b, c) => format!("VersePath::PathOT({},{},{})", a, b, c),
-        scripture_types::VersePath::PathNT(a, b, c) => format!("VersePath::PathNT({},{},{})", a, b, c),
-        scripture_types::VersePath::PathPOGP(a, b, c) => format!("VersePath::PathPOGP({},{},{})", a, b, c),
-        scripture_types::VersePath::PathDC(a, b) => format!("VersePath::PathDC({},{})", a, b),
+    let source = source.map_err(BundleError::io(src.to_path_buf()))?;
+    let missing_files = source.missing_files();
+    if !missing_files.is_empty() {
+        return Err(BundleError::MissingSourceFiles { source: source.to_string(), files: missing_files });
     }
+    Ok(source)
 }
 
-pub fn read_file(filepath: &str) -> String {
-    let file = File::open(filepath).expect("could not open file");
-    let mut buffered_reader = BufReader::new(file);
-    let mut contents = String::new();
-    let _number_of_bytes: usize = match buffered_reader.read_to_string(&mut contents) {
-        Ok(number_of_bytes) => number_of_bytes,
-        Err(_err) => 0,
-    };
+// An artifact's name, and its size in bytes before and after compression.
+type ArtifactSize = (String, usize, usize);
 
-    contents
+// What a run has written: each artifact's sizes for the report, and its hash
+// for the manifest.
+struct Written {
+    sizes: Vec<ArtifactSize>,
+    manifest: Manifest,
 }
 
-pub fn copy_minified<T: serde::de::DeserializeOwned + serde::ser::Serialize>(
-    src_folder: &std::path::PathBuf,
-    dest_folder: &std::path::PathBuf,
+// Writes an artifact behind its header (see `data_bundler::artifact`), both
+// as is and gzipped, for servers and clients that can take either.
+fn write_artifact(
+    payload: &[u8],
+    corpus_version: &str,
+    dest_folder: &Path,
     file_name: &str,
-) -> T {
-    println!("    {}", file_name);
-    let mut src = src_folder.clone();
-    src.push(file_name);
-
-    let unparsed: String = read_file(&src.into_os_string().into_string().unwrap());
-    let parsed: T = serde_json::from_str(&unparsed).unwrap();
+    written: &mut Written,
+) -> Result<(), BundleError> {
+    let bytes = &data_bundler::artifact::pack(corpus_version, payload)[..];
+    let compressed = data_bundler::compression::compress(bytes);
+    for (name, contents) in &[(file_name.to_string(), bytes), (format!("{}.gz", file_name), &compressed[..])] {
+        let dest = dest_folder.join(name);
+
+        let mut f = BufWriter::new(File::create(&dest).map_err(BundleError::io(dest.clone()))?);
+        f.write_all(contents).and_then(|_| f.flush()).map_err(BundleError::io(dest))?;
+    }
+    written.manifest.record(file_name, bytes);
+    written.manifest.record(&format!("{}.gz", file_name), &compressed);
+    written.sizes.push((file_name.to_string(), bytes.len(), compressed.len()));
+    Ok(())
+}
 
-    let mut dest = dest_folder.clone();
-    dest.push(format!("{}.bin", file_name));
+pub fn print_size_report(sizes: &[ArtifactSize]) {
+    let percent = |raw: usize, compressed: usize| if raw == 0 { 100.0 } else { 100.0 * compressed as f64 / raw as f64 };
 
-    let mut f = BufWriter::new(File::create(dest).unwrap());
-    f.write(&bincode::serialize(&parsed).unwrap()).unwrap();
-    f.flush().unwrap();
+    println!("\n    {:<36} {:>12} {:>12}", "artifact", "raw", "gzip");
+    for (file_name, raw, compressed) in sizes {
+        println!("    {:<36} {:>12} {:>12} {:>6.1}%", file_name, raw, compressed, percent(*raw, *compressed));
+    }
+    let total_raw: usize = sizes.iter().map(|(_, raw, _)| raw).sum();
+    let total_compressed: usize = sizes.iter().map(|(_, _, compressed)| compressed).sum();
+    println!(
+        "    {:<36} {:>12} {:>12} {:>6.1}%",
+        "total",
+        total_raw,
+        total_compressed,
+        percent(total_raw, total_compressed),
+    );
+}
 
-    parsed
+fn write_collection<T: serde::ser::Serialize + Versioned>(
+    collection: &T,
+    key: &str,
+    dest_folder: &Path,
+    file_name: &str,
+    written: &mut Written,
+) -> Result<(), BundleError> {
+    println!("    {}", file_name);
+    let file_name = format!("{}.bin", file_name);
+
+    write_artifact(
+        &bincode::serialize(collection).map_err(BundleError::serialization(dest_folder.join(&file_name)))?,
+        &collection_version(key, collection),
+        dest_folder,
+        &file_name,
+        written,
+    )
 }
 
-pub fn write_minified<T: serde::ser::Serialize>(
+fn write_minified<T: serde::ser::Serialize>(
     data: &T,
-    dest_folder: &std::path::PathBuf,
+    corpus_version: &str,
+    dest_folder: &Path,
     file_name: &str,
-) -> () {
-    let mut dest = dest_folder.clone();
-    dest.push(format!("{}.bin", file_name));
-
+    written: &mut Written,
+) -> Result<(), BundleError> {
     println!("writing {}", file_name);
+    let file_name = format!("{}.bin", file_name);
 
-    let mut f = BufWriter::new(File::create(dest).unwrap());
-    f.write(&bincode::serialize(&data).unwrap()).unwrap();
-    f.flush().unwrap();
+    let serialized = bincode::serialize(&data).map_err(BundleError::serialization(dest_folder.join(&file_name)))?;
+    write_artifact(&serialized, corpus_version, dest_folder, &file_name, written)
 }
 
-fn main() {
-    let mut project_root = std::env::current_exe().expect("Unable to find");
-    project_root.pop();
-    project_root.pop();
-    project_root.pop();
-
-    let mut src_folder = project_root.clone();
-    src_folder.push("node_modules");
-    src_folder.push("@bencrowder");
-    src_folder.push("scriptures-json");
-
-    let mut test_path = src_folder.clone();
-    test_path.push("old-testament.json");
-    ensure_data_source(&test_path);
-
-    let mut dest_folder = project_root.clone();
-    dest_folder.push("data-bundler");
-    dest_folder.push("data");
-
-    println!("Minifying:");
-    let ot = copy_minified::<scripture_types::OldTestament>(
-        &src_folder,
-        &dest_folder,
-        "old-testament.json",
-    );
+// The five collections, as read from scriptures-json.
+struct Canon {
+    ot: OldTestament,
+    nt: NewTestament,
+    bom: BookOfMormon,
+    dc: DoctrineAndCovenants,
+    pogp: PearlOfGreatPrice,
+}
 
-    let nt = copy_minified::<scripture_types::NewTestament>(
-        &src_folder,
-        &dest_folder,
-        "new-testament.json",
-    );
+impl Canon {
+    fn read(source: &DataSource) -> Result<Canon, BundleError> {
+        println!("Reading scriptures:");
+        Ok(Canon {
+            ot: source.read_json("old-testament.json")?,
+            nt: source.read_json("new-testament.json")?,
+            bom: source.read_json("book-of-mormon.json")?,
+            dc: source.read_json("doctrine-and-covenants.json")?,
+            pogp: source.read_json("pearl-of-great-price.json")?,
+        })
+    }
 
-    let bom = copy_minified::<scripture_types::BookOfMormon>(
-        &src_folder,
-        &dest_folder,
-        "book-of-mormon.json",
-    );
+    // Writes each collection, minified.
+    fn minify(&self, dest_folder: &Path, written: &mut Written) -> Result<(), BundleError> {
+        println!("Minifying:");
+        write_collection(&self.ot, "ot", dest_folder, "old-testament.json", written)?;
+        write_collection(&self.nt, "nt", dest_folder, "new-testament.json", written)?;
+        write_collection(&self.bom, "bom", dest_folder, "book-of-mormon.json", written)?;
+        write_collection(&self.dc, "dc", dest_folder, "doctrine-and-covenants.json", written)?;
+        write_collection(&self.pogp, "pogp", dest_folder, "pearl-of-great-price.json", written)?;
+        println!("Minifying done!\n");
+        Ok(())
+    }
 
-    let dc = copy_minified::<scripture_types::DoctrineAndCovenants>(
-        &src_folder,
-        &dest_folder,
-        "doctrine-and-covenants.json",
-    );
+    // The corpus version of artifacts built from the whole canon.
+    fn corpus_version(&self) -> String {
+        [
+            collection_version("ot", &self.ot),
+            collection_version("nt", &self.nt),
+            collection_version("bom", &self.bom),
+            collection_version("dc", &self.dc),
+            collection_version("pogp", &self.pogp),
+        ]
+        .join("\n")
+    }
 
-    let pogp = copy_minified::<scripture_types::PearlOfGreatPrice>(
-        &src_folder,
-        &dest_folder,
-        "pearl-of-great-price.json",
-    );
-    println!("Minifying done!\n");
+    fn corpus(&self) -> data_bundler::verify::Corpus<'_> {
+        data_bundler::verify::Corpus { ot: &self.ot, nt: &self.nt, bom: &self.bom, dc: &self.dc, pogp: &self.pogp }
+    }
+}
+
+type Indices = (WordsIndex, SurfaceIndex, PathsIndex, HeadingPathsIndex, FrontMatterPathsIndex);
+
+// What `index` writes, in the order `Indices` holds them.
+const INDEX_FILES: [&str; 5] = [
+    "words-index.json",
+    "surface-index.json",
+    "paths-index.json",
+    "heading-paths-index.json",
+    "front-matter-paths-index.json",
+];
 
+// A words index in key order all the way down, for writing out.
+type SortedWords<'a> = BTreeMap<&'a String, BTreeMap<&'a u16, &'a Vec<(usize, usize)>>>;
+
+fn sorted_words(words: &WordsIndex) -> SortedWords<'_> {
+    words.iter().map(|(word, usages)| (word, indices::sorted(usages))).collect()
+}
+
+// Builds the indices, and writes each of them on its own, for `codegen` to
+// assemble and for debugging.
+fn index(canon: &Canon, corpus: &str, dest_folder: &Path, written: &mut Written) -> Result<Indices, BundleError> {
     println!("Building indices:");
-    let (words_index, paths_index) = data_bundler::build_index(&ot, &nt, &bom, &dc, &pogp);
+    let (words_index, surface_index, paths_index, heading_paths_index, front_matter_paths_index) =
+        data_bundler::build_index(&canon.ot, &canon.nt, &canon.bom, &canon.dc, &canon.pogp)?;
     println!("Index building done!\n");
     println!("total word stems: {}", words_index.len());
+    println!("total surface words: {}", surface_index.len());
     println!("total paths: {}", paths_index.len());
-    write_minified(&paths_index, &dest_folder, "paths-index.json");
-    write_minified(&words_index, &dest_folder, "words-index.json");
+    println!("total headings and notes: {}", heading_paths_index.len());
+    println!("total front matter passages: {}", front_matter_paths_index.len());
+    write_minified(&indices::sorted(&paths_index), corpus, dest_folder, "paths-index.json", written)?;
+    write_minified(&indices::sorted(&heading_paths_index), corpus, dest_folder, "heading-paths-index.json", written)?;
+    write_minified(
+        &indices::sorted(&front_matter_paths_index),
+        corpus,
+        dest_folder,
+        "front-matter-paths-index.json",
+        written,
+    )?;
+    write_minified(&sorted_words(&words_index), corpus, dest_folder, "words-index.json", written)?;
+    write_minified(&sorted_words(&surface_index), corpus, dest_folder, "surface-index.json", written)?;
+
+    Ok((words_index, surface_index, paths_index, heading_paths_index, front_matter_paths_index))
+}
 
-    let mut paths_index_codegen_file = dest_folder.clone();
-    paths_index_codegen_file.push("codegen-paths-index.rs");
+// Reads back one of the files `write_minified` wrote, and its corpus version.
+fn read_minified<T: serde::de::DeserializeOwned>(dest_folder: &Path, file_name: &str) -> Result<(String, T), BundleError> {
+    let src = dest_folder.join(format!("{}.bin", file_name));
 
-    println!("generating paths index codegen file...");
+    let bytes = std::fs::read(&src).map_err(BundleError::io(src.clone()))?;
+    let (corpus_version, payload) = data_bundler::artifact::unpack(&bytes).map_err(BundleError::artifact(src.clone()))?;
+    let data = bincode::deserialize(payload).map_err(BundleError::serialization(src))?;
+    Ok((corpus_version, data))
+}
 
-    let mut paths_index_phf: phf_codegen::Map<u16> = phf_codegen::Map::new();
-    for (k, v) in &paths_index {
-        paths_index_phf.entry(*k, &repr_verse_path(v));
+// Reads back the indices `index` wrote, and the corpus they were built from.
+fn read_indices(dest_folder: &Path) -> Result<(String, Indices), BundleError> {
+    let (corpus, paths_index) = read_minified(dest_folder, "paths-index.json")?;
+    let (heading_corpus, heading_paths_index) = read_minified(dest_folder, "heading-paths-index.json")?;
+    let (front_matter_corpus, front_matter_paths_index) = read_minified(dest_folder, "front-matter-paths-index.json")?;
+    let (words_corpus, words_index) = read_minified(dest_folder, "words-index.json")?;
+    let (surface_corpus, surface_index) = read_minified(dest_folder, "surface-index.json")?;
+    if [heading_corpus, front_matter_corpus, words_corpus, surface_corpus].iter().any(|other| *other != corpus) {
+        return Err(BundleError::MixedIndices { dest: dest_folder.to_path_buf() });
     }
+    Ok((corpus, (words_index, surface_index, paths_index, heading_paths_index, front_matter_paths_index)))
+}
 
-    println!("writing paths index codegen file...");
+// Writes `index.bin`, the one index the client loads.
+fn codegen(indices: Indices, corpus: &str, dest_folder: &Path, written: &mut Written) -> Result<(), BundleError> {
+    let (words_index, surface_index, paths_index, heading_paths_index, front_matter_paths_index) = indices;
+    let stem_forms = data_bundler::build_stem_forms(&surface_index);
+    let synonyms = data_bundler::build_synonyms(SYNONYMS_TABLE);
+    println!("total synonym stems: {}", synonyms.len());
+
+    let passage_count = paths_index.len() + heading_paths_index.len() + front_matter_paths_index.len();
+    let metadata = indices::IndexMetadata {
+        stem_forms,
+        synonyms,
+        average_verse_length: data_bundler::average_verse_length(&words_index, passage_count),
+        paths: paths_index,
+        heading_paths: heading_paths_index,
+        front_matter_paths: front_matter_paths_index,
+    };
+    let index = indices::write_index(&words_index, &surface_index, &metadata)?;
+    println!("writing index.bin");
+    write_artifact(&index, corpus, dest_folder, "index.bin", written)
+}
 
-    let mut f_codegen = BufWriter::new(File::create(paths_index_codegen_file).unwrap());
+// Checks a generated index against the scripture text: every path has to
+// lead to a passage, and every highlight has to land on its word. Returns
+// whether it passed.
+fn verify(source: &DataSource, dest_folder: &Path) -> Result<bool, BundleError> {
+    use data_bundler::verify::{self, Mismatch, Passage};
+
+    let index_file = dest_folder.join("index.bin");
+    let bytes = std::fs::read(&index_file).map_err(BundleError::io(index_file.clone()))?;
+    let (index_corpus, bytes) = data_bundler::artifact::unpack(&bytes).map_err(BundleError::artifact(index_file.clone()))?;
+    let index = indices::Index::from_bytes(bytes)?;
+
+    let canon = Canon::read(source)?;
+    let corpus = canon.corpus();
+    if index_corpus != canon.corpus_version() {
+        return Err(BundleError::CorpusMismatch { path: index_file, corpus: index_corpus });
+    }
 
-    writeln!(
-        &mut f_codegen,
-        "pub static PHF_PATHS_INDEX: phf::Map<u16, scripture_types::VersePath> = \n{};\n",
-        paths_index_phf.build(),
-    ).unwrap();
+    let mut mismatches: Vec<Mismatch> = vec![];
+    let mut passages: fnv::FnvHashMap<u16, Passage> = fnv::FnvHashMap::default();
 
+    println!("Checking paths...");
+    for (scripture_id, path) in &index.metadata.paths {
+        match verify::resolve_verse(&corpus, path) {
+            Some(verse) => {
+                passages.insert(*scripture_id, (verse.reference.clone(), &verse.text));
+            },
+            None => mismatches.push(Mismatch::UnresolvedPath { scripture_id: *scripture_id, path: format!("{:?}", path) }),
+        }
+    }
+    for (scripture_id, path) in &index.metadata.heading_paths {
+        match verify::resolve_heading(&corpus, path) {
+            Some(passage) => {
+                passages.insert(*scripture_id, passage);
+            },
+            None => mismatches.push(Mismatch::UnresolvedPath { scripture_id: *scripture_id, path: format!("{:?}", path) }),
+        }
+    }
+    for (scripture_id, path) in &index.metadata.front_matter_paths {
+        match verify::resolve_front_matter(&corpus, path) {
+            Some(passage) => {
+                passages.insert(*scripture_id, passage);
+            },
+            None => mismatches.push(Mismatch::UnresolvedPath { scripture_id: *scripture_id, path: format!("{:?}", path) }),
+        }
+    }
 
+    println!("Checking postings...");
+    let stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
+    let stem = |found: &str| data_bundler::stem_word(&stemmer, found);
+    for (word, postings) in index.stems() {
+        mismatches.extend(verify::verify_postings(&word, postings.entries(), &passages, &stem));
+    }
+    let surface = |found: &str| found.to_string();
+    for (word, postings) in index.surface_forms() {
+        mismatches.extend(verify::verify_postings(&word, postings.entries(), &passages, &surface));
+    }
 
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    println!("{} passages checked, {} mismatches", passages.len(), mismatches.len());
+    Ok(mismatches.is_empty())
+}
 
-    let mut words_index_codegen_file = dest_folder.clone();
-    words_index_codegen_file.push("codegen-words-index.rs");
+// Run from the project root, where `npm i` installs scriptures-json.
+const DEFAULT_SRC: &str = "node_modules/@bencrowder/scriptures-json";
+const DEFAULT_DEST: &str = "data-bundler/data";
+
+fn stage<'a, 'b>(name: &'a str, about: &'a str, reads_source: bool) -> App<'a, 'b> {
+    let stage = SubCommand::with_name(name).about(about).arg(
+        Arg::with_name("dest")
+            .long("dest")
+            .value_name("DIR")
+            .default_value(DEFAULT_DEST)
+            .help("Where the bundled data goes"),
+    );
+    if !reads_source {
+        return stage;
+    }
+    stage.arg(
+        Arg::with_name("src")
+            .long("src")
+            .value_name("DIR")
+            .default_value(DEFAULT_SRC)
+            .help("A directory or tarball of the scriptures-json files"),
+    )
+    .arg(
+        Arg::with_name("install")
+            .long("install")
+            .help("Installs scriptures-json with npm if the source is missing any files"),
+    )
+}
 
-    println!("generating words index codegen file...");
+// The files a stage writes, given their names without `.bin`.
+fn artifact_files(names: &[&str]) -> Vec<String> {
+    names.iter().flat_map(|name| vec![format!("{}.bin", name), format!("{}.bin.gz", name)]).collect()
+}
 
-    let mut words_index_phf: phf_codegen::Map<&str> = phf_codegen::Map::new();
-    for (word, usage_map) in &words_index {
-        let mut usages_phf: phf_codegen::Map<u16> = phf_codegen::Map::new();
-        for (scripture_id, highlights_vec) in usage_map {
-            let (i_s, l_s): (Vec<_>, Vec<_>) = highlights_vec.iter().cloned().map(|(x,y)| (x as u16, y as u8)).unzip();
-            usages_phf.entry(
-                *scripture_id,
-                &format!(
-                    "(U256 {{ 0: {:?} }},{})",
-                    data_bundler::pack_indices_arr(&i_s),
-                    data_bundler::pack_lengths(&l_s),
-                ),
-            );
+// The bundler is one of every stage's inputs, so that changing how the data
+// is built rebuilds it.
+fn code_version() -> String {
+    std::env::current_exe()
+        .and_then(std::fs::read)
+        .map(|exe| sha256(&exe))
+        .unwrap_or_else(|_| env!("CARGO_PKG_VERSION").to_string())
+}
+
+fn force_flag<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("force").long("force").help("Rebuilds even what's up to date")
+}
+
+// Runs one subcommand, and returns whether it passed.
+fn run(command: &str, args: &ArgMatches) -> Result<bool, BundleError> {
+    let dest_folder = PathBuf::from(args.value_of("dest").unwrap());
+    let source = match args.value_of("src") {
+        Some(src) => Some(open_source(Path::new(src), args.is_present("install"))?),
+        None => None,
+    };
+
+    if command == "verify" {
+        return verify(source.as_ref().unwrap(), &dest_folder);
+    }
+    if command == "check" {
+        let manifest = Manifest::read(&dest_folder)?;
+        let mismatches = manifest.check(&dest_folder);
+        for mismatch in &mismatches {
+            println!("{}", mismatch);
         }
-        let built_usages_phf = usages_phf.build();
-        words_index_phf.entry(word, &built_usages_phf.to_string());
+        println!("{} artifacts checked, {} mismatches", manifest.artifacts.len(), mismatches.len());
+        return Ok(mismatches.is_empty());
     }
 
+    std::fs::create_dir_all(&dest_folder).map_err(BundleError::io(dest_folder.clone()))?;
+    let force = args.is_present("force");
+    let code = code_version();
+    let mut written = Written { sizes: vec![], manifest: Manifest::read(&dest_folder)? };
+    let mut stage_inputs = StageInputs::read(&dest_folder)?;
+
+    // minify and index both only depend on the scriptures, so they share
+    // their inputs, and a run that does both only reads the scriptures once.
+    let mut built = None;
+    if let Some(source) = &source {
+        let inputs = hash_inputs(&[code.as_bytes(), source.hash()?.as_bytes()]);
+        let stale = |stage: &str, outputs: &[String]| {
+            force || !stage_inputs.is_fresh(stage, &inputs, outputs, &written.manifest, &dest_folder)
+        };
+        let minify = command != "index" && stale("minify", &artifact_files(&SOURCE_FILES));
+        let build = command != "minify" && stale("index", &artifact_files(&INDEX_FILES));
+        if command != "index" && !minify {
+            println!("Collections are up to date, skipping minify.");
+        }
+        if command != "minify" && !build {
+            println!("Indices are up to date, skipping index.");
+        }
 
-    println!("writing words index codegen file...");
+        if minify || build {
+            let canon = Canon::read(source)?;
+            let corpus = canon.corpus_version();
+            written.manifest.set_corpus(&corpus);
+            if minify {
+                canon.minify(&dest_folder, &mut written)?;
+                stage_inputs.record("minify", &inputs);
+            }
+            if build {
+                built = Some((corpus.clone(), index(&canon, &corpus, &dest_folder, &mut written)?));
+                stage_inputs.record("index", &inputs);
+            }
+        }
+    }
+
+    if command == "codegen" || command == "all" {
+        // The indices are only identified by their hashes in the manifest;
+        // they're read once codegen is known to be needed.
+        let index_hashes: Vec<&[u8]> = artifact_files(&INDEX_FILES)
+            .iter()
+            .map(|file_name| written.manifest.artifacts.get(file_name).map(|hash| hash.as_bytes()).unwrap_or(b""))
+            .collect();
+        let inputs = hash_inputs(&[&[code.as_bytes(), SYNONYMS_TABLE.as_bytes()], &index_hashes[..]].concat());
+        let outputs = artifact_files(&["index"]);
+        if !force && stage_inputs.is_fresh("codegen", &inputs, &outputs, &written.manifest, &dest_folder) {
+            println!("index.bin is up to date, skipping codegen.");
+        } else {
+            let (corpus, indices) = match built {
+                Some(built) => built,
+                None => read_indices(&dest_folder)?,
+            };
+            written.manifest.set_corpus(&corpus);
+            codegen(indices, &corpus, &dest_folder, &mut written)?;
+            stage_inputs.record("codegen", &inputs);
+        }
+    }
 
-    let mut f_codegen_words_index = BufWriter::new(File::create(words_index_codegen_file).unwrap());
+    if !written.sizes.is_empty() {
+        print_size_report(&written.sizes);
+    }
+    println!("writing {}", MANIFEST_FILE);
+    written.manifest.write(&dest_folder)?;
+    stage_inputs.write(&dest_folder)?;
+    Ok(true)
+}
 
-    writeln!(
-        &mut f_codegen_words_index,
-        "pub static PHF_WORDS_INDEX: phf::Map<&str, phf::Map<u16, (U256,u128)>> = \n{};\n",
-        words_index_phf.build(),
-    ).unwrap();
+fn main() {
+    let matches = App::new("data-bundler")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Bundles scriptures-json into the files the client loads")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(stage("minify", "Writes each collection, minified", true).arg(force_flag()))
+        .subcommand(stage("index", "Builds the search indices, and writes each of them on its own", true).arg(force_flag()))
+        .subcommand(stage("codegen", "Assembles the indices written by `index` into index.bin", false).arg(force_flag()))
+        .subcommand(stage("all", "Runs minify, index and codegen", true).arg(force_flag()))
+        .subcommand(stage("verify", "Checks index.bin against the scriptures", true))
+        .subcommand(stage("check", "Checks the bundled data against its manifest", false))
+        .get_matches();
+    let (command, args) = matches.subcommand();
+
+    match run(command, args.expect("a subcommand is required")) {
+        Ok(true) => {},
+        Ok(false) => std::process::exit(1),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            std::process::exit(1);
+        },
+    }
 }
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::artifact::SCHEMA_VERSION;
+use crate::error::BundleError;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+// Which release of scriptures-json a collection came from.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Release {
+    pub version: u8,
+    pub last_modified: String,
+}
+
+// What a data folder holds: the corpus its artifacts were built from, and
+// the SHA-256 of each artifact, so that two builds can be compared and a
+// release can be checked against what was built.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Manifest {
+    pub schema_version: u32,
+    pub corpus: BTreeMap<String, Release>,
+    pub artifacts: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ManifestMismatch {
+    Missing { file_name: String },
+    Changed { file_name: String, expected: String, found: String },
+}
+
+impl fmt::Display for ManifestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManifestMismatch::Missing { file_name } => write!(f, "{} is missing", file_name),
+            ManifestMismatch::Changed { file_name, expected, found } => {
+                write!(f, "{} has sha256 {}, but the manifest says {}", file_name, found, expected)
+            },
+        }
+    }
+}
+
+pub fn sha256(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+// Reads a corpus version, as written by `artifact::collection_version`.
+fn parse_corpus(corpus_version: &str) -> BTreeMap<String, Release> {
+    corpus_version
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let key = fields.next()?;
+            let version = fields.next()?.parse().ok()?;
+            let last_modified = fields.next()?;
+            Some((key.to_string(), Release { version, last_modified: last_modified.to_string() }))
+        })
+        .collect()
+}
+
+impl Manifest {
+    // An empty manifest if the folder doesn't have one yet.
+    pub fn read(dest_folder: &Path) -> Result<Manifest, BundleError> {
+        let path = dest_folder.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let unparsed = std::fs::read_to_string(&path).map_err(BundleError::io(path.clone()))?;
+        serde_json::from_str(&unparsed).map_err(BundleError::malformed_json(path))
+    }
+
+    pub fn write(&self, dest_folder: &Path) -> Result<(), BundleError> {
+        let path = dest_folder.join(MANIFEST_FILE);
+        // Maps and structs are all in a fixed order, so this can't fail.
+        let mut json = serde_json::to_string_pretty(self).unwrap();
+        json.push('\n');
+        std::fs::write(&path, json).map_err(BundleError::io(path))
+    }
+
+    // Artifacts built from another corpus, or with another schema, are
+    // dropped: whatever stage wrote them has to run again.
+    pub fn set_corpus(&mut self, corpus_version: &str) {
+        let corpus = parse_corpus(corpus_version);
+        if self.corpus != corpus || self.schema_version != SCHEMA_VERSION {
+            self.artifacts.clear();
+        }
+        self.schema_version = SCHEMA_VERSION;
+        self.corpus = corpus;
+    }
+
+    pub fn record(&mut self, file_name: &str, bytes: &[u8]) {
+        self.artifacts.insert(file_name.to_string(), sha256(bytes));
+    }
+
+    fn check_file(dest_folder: &Path, file_name: &str, expected: &str) -> Option<ManifestMismatch> {
+        match std::fs::read(dest_folder.join(file_name)) {
+            Err(_) => Some(ManifestMismatch::Missing { file_name: file_name.to_string() }),
+            Ok(bytes) => {
+                let found = sha256(&bytes);
+                if found == expected {
+                    None
+                } else {
+                    Some(ManifestMismatch::Changed { file_name: file_name.to_string(), expected: expected.to_string(), found })
+                }
+            },
+        }
+    }
+
+    // Hashes each artifact in the folder again, and returns the ones that
+    // don't match.
+    pub fn check(&self, dest_folder: &Path) -> Vec<ManifestMismatch> {
+        self.artifacts
+            .iter()
+            .filter_map(|(file_name, expected)| Manifest::check_file(dest_folder, file_name, expected))
+            .collect()
+    }
+
+    // Whether an artifact is in the manifest, and still matches it.
+    pub fn is_intact(&self, dest_folder: &Path, file_name: &str) -> bool {
+        match self.artifacts.get(file_name) {
+            Some(expected) => Manifest::check_file(dest_folder, file_name, expected).is_none(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORPUS: &str = "ot 1 2019-11-08\nbom 2 2020-01-01";
+
+    #[test]
+    fn keeps_artifacts_until_the_corpus_changes() {
+        let mut manifest = Manifest::default();
+        manifest.set_corpus(CORPUS);
+        manifest.record("index.bin", b"index");
+        assert_eq!(Some(&Release { version: 2, last_modified: String::from("2020-01-01") }), manifest.corpus.get("bom"));
+        assert_eq!(Some(&sha256(b"index")), manifest.artifacts.get("index.bin"));
+
+        manifest.set_corpus(CORPUS);
+        assert_eq!(1, manifest.artifacts.len());
+        manifest.set_corpus("ot 1 2019-11-08\nbom 3 2020-06-01");
+        assert!(manifest.artifacts.is_empty());
+    }
+
+    #[test]
+    fn checks_artifacts_against_their_hashes() {
+        let dest_folder = std::env::temp_dir().join(format!("data-bundler-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dest_folder).unwrap();
+        std::fs::write(dest_folder.join("index.bin"), b"index").unwrap();
+        std::fs::write(dest_folder.join("book-of-mormon.json.bin"), b"tampered").unwrap();
+
+        let mut manifest = Manifest::default();
+        manifest.set_corpus(CORPUS);
+        manifest.record("index.bin", b"index");
+        manifest.record("book-of-mormon.json.bin", b"book of mormon");
+        manifest.record("old-testament.json.bin", b"old testament");
+        manifest.write(&dest_folder).unwrap();
+
+        let manifest = Manifest::read(&dest_folder).unwrap();
+        assert_eq!(
+            vec![
+                ManifestMismatch::Changed {
+                    file_name: String::from("book-of-mormon.json.bin"),
+                    expected: sha256(b"book of mormon"),
+                    found: sha256(b"tampered"),
+                },
+                ManifestMismatch::Missing { file_name: String::from("old-testament.json.bin") },
+            ],
+            manifest.check(&dest_folder),
+        );
+
+        std::fs::remove_dir_all(&dest_folder).unwrap();
+    }
+}
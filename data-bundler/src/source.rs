@@ -0,0 +1,161 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use fnv::FnvHashMap;
+
+use crate::compression;
+use crate::error::BundleError;
+use crate::incremental::hash_inputs;
+
+// The files of scriptures-json that get bundled.
+pub const SOURCE_FILES: [&str; 5] = [
+    "old-testament.json",
+    "new-testament.json",
+    "book-of-mormon.json",
+    "doctrine-and-covenants.json",
+    "pearl-of-great-price.json",
+];
+
+// Where the scriptures-json files are read from: a directory, like the one
+// `npm i` installs, or a tarball, like the one `npm pack` downloads.
+pub enum DataSource {
+    Directory(PathBuf),
+    Tarball { path: PathBuf, files: FnvHashMap<String, String> },
+}
+
+impl DataSource {
+    // Tarballs may be gzipped, and are read into memory up front.
+    pub fn open(path: &Path) -> io::Result<DataSource> {
+        if path.is_dir() {
+            return Ok(DataSource::Directory(path.to_path_buf()));
+        }
+        let bytes = compression::decompress(std::fs::read(path)?)?;
+        Ok(DataSource::Tarball { path: path.to_path_buf(), files: read_tarball(&bytes[..])? })
+    }
+
+    // Which of `SOURCE_FILES` aren't there.
+    pub fn missing_files(&self) -> Vec<&'static str> {
+        SOURCE_FILES
+            .iter()
+            .filter(|file_name| match self {
+                DataSource::Directory(path) => !path.join(file_name).is_file(),
+                DataSource::Tarball { files, .. } => !files.contains_key(**file_name),
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Where a file is, for error messages. Files in tarballs are shown as if
+    // the tarball were a directory.
+    pub fn path(&self, file_name: &str) -> PathBuf {
+        match self {
+            DataSource::Directory(path) | DataSource::Tarball { path, .. } => path.join(file_name),
+        }
+    }
+
+    pub fn read(&self, file_name: &str) -> io::Result<String> {
+        match self {
+            DataSource::Directory(path) => std::fs::read_to_string(path.join(file_name)),
+            DataSource::Tarball { path, files } => files.get(file_name).cloned().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("{} isn't in {}", file_name, path.display()))
+            }),
+        }
+    }
+
+    pub fn read_json<T: serde::de::DeserializeOwned>(&self, file_name: &str) -> Result<T, BundleError> {
+        let unparsed = self.read(file_name).map_err(BundleError::io(self.path(file_name)))?;
+        serde_json::from_str(&unparsed).map_err(BundleError::malformed_json(self.path(file_name)))
+    }
+
+    // A hash of every source file, to tell whether any of them changed.
+    pub fn hash(&self) -> Result<String, BundleError> {
+        let contents = SOURCE_FILES
+            .iter()
+            .map(|file_name| self.read(file_name).map_err(BundleError::io(self.path(file_name))))
+            .collect::<Result<Vec<String>, BundleError>>()?;
+        Ok(hash_inputs(&contents.iter().map(|content| content.as_bytes()).collect::<Vec<&[u8]>>()))
+    }
+}
+
+impl fmt::Display for DataSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataSource::Directory(path) | DataSource::Tarball { path, .. } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+// Reads the `SOURCE_FILES` out of an uncompressed tarball, wherever they are
+// in it; npm puts them under `package/`.
+fn read_tarball<R: Read>(tarball: R) -> io::Result<FnvHashMap<String, String>> {
+    let mut files = FnvHashMap::default();
+    for entry in tar::Archive::new(tarball).entries()? {
+        let mut entry = entry?;
+        let file_name = match entry.path()?.file_name().and_then(|file_name| file_name.to_str()) {
+            Some(file_name) if SOURCE_FILES.contains(&file_name) => file_name.to_string(),
+            _ => continue,
+        };
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents)?;
+        files.insert(file_name, contents);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, contents.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn reads_source_files_out_of_tarballs() {
+        let bytes = tarball(&[
+            ("package/package.json", "{}"),
+            ("package/book-of-mormon.json", "{\"books\": []}"),
+            ("package/doctrine-and-covenants.json", "{}"),
+        ]);
+        let source = DataSource::Tarball { path: PathBuf::from("scriptures.tgz"), files: read_tarball(&bytes[..]).unwrap() };
+
+        assert_eq!("{\"books\": []}", source.read("book-of-mormon.json").unwrap());
+        match source.read_json::<scripture_types::DoctrineAndCovenants>("doctrine-and-covenants.json") {
+            Err(BundleError::MalformedJson { path, line: 1, column: 2, .. }) => {
+                assert_eq!(PathBuf::from("scriptures.tgz/doctrine-and-covenants.json"), path);
+            },
+            other => panic!("expected malformed JSON, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(io::ErrorKind::NotFound, source.read("package.json").unwrap_err().kind());
+        assert_eq!(
+            vec!["old-testament.json", "new-testament.json", "pearl-of-great-price.json"],
+            source.missing_files(),
+        );
+    }
+
+    #[test]
+    fn opens_gzipped_tarballs_and_directories() {
+        let directory = std::env::temp_dir().join(format!("data-bundler-source-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let tarball_path = directory.join("scriptures.tgz");
+        std::fs::write(&tarball_path, compression::compress(&tarball(&[("old-testament.json", "{}")]))).unwrap();
+
+        let source = DataSource::open(&tarball_path).unwrap();
+        assert_eq!("{}", source.read("old-testament.json").unwrap());
+        assert_eq!(4, source.missing_files().len());
+
+        let source = DataSource::open(&directory).unwrap();
+        assert_eq!(SOURCE_FILES.to_vec(), source.missing_files());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+
+use fnv::FnvHashMap;
+use scripture_types::{
+    Book, BookOfMormon, Chapter, DoctrineAndCovenants, FrontMatterPath, HeadingField, HeadingPath, NewTestament,
+    OldTestament, PearlOfGreatPrice, Verse, VersePath,
+};
+
+use crate::{decode_highlights, PostingError};
+
+// The five collections, for looking passages back up from their paths.
+pub struct Corpus<'a> {
+    pub ot: &'a OldTestament,
+    pub nt: &'a NewTestament,
+    pub bom: &'a BookOfMormon,
+    pub dc: &'a DoctrineAndCovenants,
+    pub pogp: &'a PearlOfGreatPrice,
+}
+
+// A passage's reference, and the text its postings point into.
+pub type Passage<'a> = (String, &'a String);
+
+fn resolve_book<'a>(corpus: &Corpus<'a>, path: &VersePath) -> Option<&'a Book> {
+    match path {
+        VersePath::PathOT(b, _, _) => corpus.ot.books.get(*b as usize),
+        VersePath::PathNT(b, _, _) => corpus.nt.books.get(*b as usize),
+        VersePath::PathBoM(b, _, _) => corpus.bom.books.get(*b as usize),
+        VersePath::PathDC(_, _) => None,
+        VersePath::PathPOGP(b, _, _) => corpus.pogp.books.get(*b as usize),
+    }
+}
+
+fn resolve_chapter<'a>(corpus: &Corpus<'a>, path: &VersePath) -> Option<&'a Chapter> {
+    match path {
+        VersePath::PathOT(_, c, _)
+        | VersePath::PathNT(_, c, _)
+        | VersePath::PathBoM(_, c, _)
+        | VersePath::PathPOGP(_, c, _) => resolve_book(corpus, path)?.chapters.get(*c as usize),
+        VersePath::PathDC(_, _) => None,
+    }
+}
+
+pub fn resolve_verse<'a>(corpus: &Corpus<'a>, path: &VersePath) -> Option<&'a Verse> {
+    match path {
+        VersePath::PathOT(_, _, v)
+        | VersePath::PathNT(_, _, v)
+        | VersePath::PathBoM(_, _, v)
+        | VersePath::PathPOGP(_, _, v) => resolve_chapter(corpus, path)?.verses.get(*v as usize),
+        VersePath::PathDC(s, v) => corpus.dc.sections.get(*s as usize)?.verses.get(*v as usize),
+    }
+}
+
+pub fn resolve_heading<'a>(corpus: &Corpus<'a>, path: &HeadingPath) -> Option<Passage<'a>> {
+    match path.field {
+        HeadingField::BookHeading | HeadingField::BookNote => {
+            let book = resolve_book(corpus, &path.verse_path)?;
+            let text = if path.field == HeadingField::BookHeading { &book.heading } else { &book.note };
+            Some((book.book.clone(), text.as_ref()?))
+        },
+        HeadingField::ChapterHeading | HeadingField::ChapterNote => {
+            let chapter = resolve_chapter(corpus, &path.verse_path)?;
+            let text = if path.field == HeadingField::ChapterHeading { &chapter.heading } else { &chapter.note };
+            Some((chapter.reference.clone(), text.as_ref()?))
+        },
+        HeadingField::VerseHeading | HeadingField::VerseSubheading => {
+            let verse = resolve_verse(corpus, &path.verse_path)?;
+            let text = if path.field == HeadingField::VerseHeading { &verse.heading } else { &verse.subheading };
+            Some((verse.reference.clone(), text.as_ref()?))
+        },
+    }
+}
+
+pub fn resolve_front_matter<'a>(corpus: &Corpus<'a>, path: &FrontMatterPath) -> Option<Passage<'a>> {
+    match path {
+        FrontMatterPath::BoMTitlePage(paragraph) => {
+            let title_page = &corpus.bom.title_page;
+            Some((title_page.title.clone(), title_page.text.get(*paragraph as usize)?))
+        },
+        FrontMatterPath::BoMTestimony(t) => {
+            let testimony = corpus.bom.testimonies.get(*t as usize)?;
+            Some((testimony.title.clone(), &testimony.text))
+        },
+        FrontMatterPath::NTTitlePage => {
+            let title_page = &corpus.nt.title_page;
+            Some((title_page.title.clone(), &title_page.text))
+        },
+        FrontMatterPath::FacsimileExplanation(b, f, e) => {
+            let facsimile = corpus.pogp.books.get(*b as usize)?.facsimiles.as_ref()?.get(*f as usize)?;
+            Some((facsimile.title.clone(), facsimile.explanations.get(*e as usize)?))
+        },
+        FrontMatterPath::DCSignature(s) => {
+            let section = corpus.dc.sections.get(*s as usize)?;
+            Some((section.reference.clone(), section.signature.as_ref()?))
+        },
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Mismatch {
+    // A path that doesn't lead to any passage in the corpus.
+    UnresolvedPath { scripture_id: u16, path: String },
+    // A posting for a scripture id that no path accounts for.
+    UnknownScripture { word: String, scripture_id: u16 },
+    Undecodable { word: String, reference: String, error: PostingError },
+    OutOfBounds { word: String, reference: String, highlight: (usize, usize) },
+    WrongWord { word: String, reference: String, highlight: (usize, usize), found: String },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::UnresolvedPath { scripture_id, path } => {
+                write!(f, "scripture {}: {} isn't in the corpus", scripture_id, path)
+            },
+            Mismatch::UnknownScripture { word, scripture_id } => {
+                write!(f, "{:?}: scripture {} has no path", word, scripture_id)
+            },
+            Mismatch::Undecodable { word, reference, error } => {
+                write!(f, "{} {:?}: posting can't be decoded ({:?})", reference, word, error)
+            },
+            Mismatch::OutOfBounds { word, reference, highlight: (offset, length) } => {
+                write!(f, "{} {:?}: {} bytes at {} run off the text", reference, word, length, offset)
+            },
+            Mismatch::WrongWord { word, reference, highlight: (offset, length), found } => {
+                write!(f, "{} {:?}: {} bytes at {} are {:?}", reference, word, length, offset, found)
+            },
+        }
+    }
+}
+
+// Checks one word's postings against the passages they point into. Every
+// highlight has to cover text that `normalize` turns back into `word`.
+pub fn verify_postings<'p>(
+    word: &str,
+    postings: impl Iterator<Item = (u16, &'p [u8])>,
+    passages: &FnvHashMap<u16, Passage>,
+    normalize: &dyn Fn(&str) -> String,
+) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+    for (scripture_id, posting) in postings {
+        let (reference, text) = match passages.get(&scripture_id) {
+            Some(passage) => passage,
+            None => {
+                mismatches.push(Mismatch::UnknownScripture { word: word.to_string(), scripture_id });
+                continue;
+            },
+        };
+
+        let highlights = match decode_highlights(posting) {
+            Ok(highlights) => highlights,
+            Err(error) => {
+                mismatches.push(Mismatch::Undecodable { word: word.to_string(), reference: reference.clone(), error });
+                continue;
+            },
+        };
+
+        for highlight in highlights {
+            let (offset, length) = highlight;
+            match offset.checked_add(length).and_then(|end| text.get(offset..end)) {
+                None => mismatches.push(Mismatch::OutOfBounds {
+                    word: word.to_string(),
+                    reference: reference.clone(),
+                    highlight,
+                }),
+                Some(found) if normalize(found) != word => mismatches.push(Mismatch::WrongWord {
+                    word: word.to_string(),
+                    reference: reference.clone(),
+                    highlight,
+                    found: found.to_string(),
+                }),
+                Some(_) => {},
+            }
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_highlights;
+
+    fn verify(word: &str, postings: Vec<(u16, Vec<u8>)>, passages: &FnvHashMap<u16, Passage>) -> Vec<Mismatch> {
+        let lowercase = |found: &str| found.to_lowercase();
+        verify_postings(
+            word,
+            postings.iter().map(|(scripture_id, posting)| (*scripture_id, &posting[..])),
+            passages,
+            &lowercase,
+        )
+    }
+
+    #[test]
+    fn reports_each_kind_of_mismatch() {
+        let text = String::from("Faith, hope and charity");
+        let mut passages: FnvHashMap<u16, Passage> = FnvHashMap::default();
+        passages.insert(1, (String::from("Moroni 7:1"), &text));
+
+        assert_eq!(Vec::<Mismatch>::new(), verify("faith", vec![(1, encode_highlights(&vec![(0, 5)]).unwrap())], &passages));
+        assert_eq!(
+            vec![Mismatch::WrongWord {
+                word: String::from("faith"),
+                reference: String::from("Moroni 7:1"),
+                highlight: (7, 4),
+                found: String::from("hope"),
+            }],
+            verify("faith", vec![(1, encode_highlights(&vec![(0, 5), (7, 4)]).unwrap())], &passages),
+        );
+        assert_eq!(
+            vec![Mismatch::OutOfBounds {
+                word: String::from("charity"),
+                reference: String::from("Moroni 7:1"),
+                highlight: (16, 20),
+            }],
+            verify("charity", vec![(1, encode_highlights(&vec![(16, 20)]).unwrap())], &passages),
+        );
+        assert_eq!(
+            vec![Mismatch::Undecodable {
+                word: String::from("hope"),
+                reference: String::from("Moroni 7:1"),
+                error: PostingError::Truncated,
+            }],
+            verify("hope", vec![(1, vec![7])], &passages),
+        );
+        assert_eq!(
+            vec![Mismatch::UnknownScripture { word: String::from("hope"), scripture_id: 2 }],
+            verify("hope", vec![(2, encode_highlights(&vec![(7, 4)]).unwrap())], &passages),
+        );
+    }
+}
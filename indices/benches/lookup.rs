@@ -0,0 +1,118 @@
+// Times word lookups in the binary index against a `phf::Map` over the same
+// words, which is what the generated indices used to be. Run it with
+// `cargo bench -p indices`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use fnv::FnvHashMap;
+use indices::{write_index, Index, IndexMetadata};
+use scripture_types::WordsIndex;
+
+// About as many surface forms as the scriptures have.
+const WORDS: usize = 30_000;
+const ROUNDS: usize = 20;
+
+// Made up words of three to twelve letters, always the same ones.
+fn make_words() -> Vec<String> {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    let mut words: Vec<String> = (0..WORDS)
+        .map(|_| {
+            let length = 3 + (next() % 10) as usize;
+            (0..length).map(|_| (b'a' + (next() % 26) as u8) as char).collect()
+        })
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+type Usages = Vec<(u16, Vec<(usize, usize)>)>;
+
+// What `phf::Map::get` does, over a map built the way phf_codegen builds one:
+// the entries are laid out in hash order.
+struct PhfMap {
+    key: phf_shared::HashKey,
+    disps: Vec<(u32, u32)>,
+    entries: Vec<(String, Usages)>,
+}
+
+impl PhfMap {
+    fn get(&self, word: &str) -> Option<&Usages> {
+        let hashes = phf_shared::hash(word, &self.key);
+        let index = phf_shared::get_index(&hashes, &self.disps, self.entries.len());
+        let (key, value) = &self.entries[index as usize];
+        if key == word {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+fn time(name: &str, lookups: usize, mut run: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        run();
+    }
+    let nanos = start.elapsed().as_nanos() as f64 / (ROUNDS * lookups) as f64;
+    println!("{:>16}: {:>6.1} ns per lookup", name, nanos);
+}
+
+fn main() {
+    let words = make_words();
+    let missing: Vec<String> = words.iter().map(|word| format!("{}q", word)).collect();
+
+    let mut words_index: WordsIndex = FnvHashMap::default();
+    for (i, word) in words.iter().enumerate() {
+        let usages = words_index.entry(word.to_string()).or_default();
+        for scripture_id in (i % 40..i % 40 + 3).map(|id| id as u16 * 97) {
+            usages.insert(scripture_id, vec![(i % 300, word.len())]);
+        }
+    }
+    let bytes = write_index(&words_index, &FnvHashMap::default(), &IndexMetadata::default()).unwrap();
+    let start = Instant::now();
+    let index = Index::from_bytes(&bytes).unwrap();
+    println!("loaded {} bytes in {:.1} ms", bytes.len(), start.elapsed().as_secs_f64() * 1000.0);
+
+    let state = phf_generator::generate_hash(&words);
+    let entries = state
+        .map
+        .iter()
+        .map(|i| {
+            let word = &words[*i];
+            let mut usages: Usages = words_index[word].iter().map(|(id, highlights)| (*id, highlights.clone())).collect();
+            usages.sort();
+            (word.to_string(), usages)
+        })
+        .collect();
+    let phf = PhfMap { key: state.key, disps: state.disps, entries };
+
+    println!("{} words, {} rounds", words.len(), ROUNDS);
+    time("index, found", words.len(), || {
+        for word in &words {
+            black_box(index.word(black_box(word)));
+        }
+    });
+    time("phf, found", words.len(), || {
+        for word in &words {
+            black_box(phf.get(black_box(word)));
+        }
+    });
+    time("index, missing", missing.len(), || {
+        for word in &missing {
+            black_box(index.word(black_box(word)));
+        }
+    });
+    time("phf, missing", missing.len(), || {
+        for word in &missing {
+            black_box(phf.get(black_box(word)));
+        }
+    });
+}
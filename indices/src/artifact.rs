@@ -0,0 +1,169 @@
+use std::fmt;
+
+use scripture_types::Versioned;
+
+// Every bundled file starts with a header, all numbers little-endian:
+//
+//     magic           4 bytes, `MAGIC`
+//     schema version  u32, `SCHEMA_VERSION` when it was written
+//     checksum        u32, the CRC-32 of the payload
+//     corpus length   u32
+//     corpus version  utf-8, see `collection_version`
+//
+// and then the payload: bincode, or an index from `write_index`.
+
+pub const MAGIC: [u8; 4] = *b"SCRP";
+
+// Bump whenever `scripture_types` or the index format changes, so that old
+// clients turn new data away instead of misreading it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const HEADER_LENGTH: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum ArtifactError {
+    // Doesn't start with `MAGIC`, so it isn't a bundled file at all.
+    NotAnArtifact,
+    SchemaMismatch { found: u32, expected: u32 },
+    ChecksumMismatch { found: u32, expected: u32 },
+    // The file stopped partway through its header.
+    Truncated,
+    // A collection and the index were built from different releases.
+    CorpusMismatch { collection: String, index: String },
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArtifactError::NotAnArtifact => write!(f, "not a bundled data file"),
+            ArtifactError::SchemaMismatch { found, expected } => write!(
+                f,
+                "data was bundled with schema version {}, but this client reads version {}; rebuild the data and client together",
+                found, expected,
+            ),
+            ArtifactError::ChecksumMismatch { found, expected } => {
+                write!(f, "data is corrupt (checksum {:08x}, expected {:08x})", found, expected)
+            },
+            ArtifactError::Truncated => write!(f, "data is truncated"),
+            ArtifactError::CorpusMismatch { collection, index } => write!(
+                f,
+                "{:?} isn't the release the index was built from ({:?}); rebundle the data",
+                collection, index,
+            ),
+        }
+    }
+}
+
+// A collection's key (as in the client's `IncludedSources`), version and
+// modification date. The corpus version of files built from the whole canon
+// has one of these lines for each collection.
+pub fn collection_version<T: Versioned>(key: &str, collection: &T) -> String {
+    format!("{} {} {}", key, collection.version(), collection.last_modified())
+}
+
+fn checksum(payload: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(payload);
+    crc.sum()
+}
+
+fn read_u32(bytes: &[u8], position: usize) -> Result<u32, ArtifactError> {
+    let mut word = [0; 4];
+    word.copy_from_slice(bytes.get(position..position + 4).ok_or(ArtifactError::Truncated)?);
+    Ok(u32::from_le_bytes(word))
+}
+
+pub fn pack(corpus_version: &str, payload: &[u8]) -> Vec<u8> {
+    let mut packed = Vec::with_capacity(HEADER_LENGTH + corpus_version.len() + payload.len());
+    packed.extend(&MAGIC);
+    packed.extend(&SCHEMA_VERSION.to_le_bytes());
+    packed.extend(&checksum(payload).to_le_bytes());
+    packed.extend(&(corpus_version.len() as u32).to_le_bytes());
+    packed.extend(corpus_version.as_bytes());
+    packed.extend(payload);
+    packed
+}
+
+// Checks a bundled file's header, and returns its corpus version and payload.
+pub fn unpack(bytes: &[u8]) -> Result<(String, &[u8]), ArtifactError> {
+    if !bytes.starts_with(&MAGIC) {
+        return Err(ArtifactError::NotAnArtifact);
+    }
+    let schema_version = read_u32(bytes, 4)?;
+    if schema_version != SCHEMA_VERSION {
+        return Err(ArtifactError::SchemaMismatch { found: schema_version, expected: SCHEMA_VERSION });
+    }
+    let expected_checksum = read_u32(bytes, 8)?;
+    let corpus_length = read_u32(bytes, 12)? as usize;
+    // On wasm32 a corrupt length near u32::MAX would overflow a usize.
+    let payload_start = HEADER_LENGTH.checked_add(corpus_length).ok_or(ArtifactError::Truncated)?;
+    let corpus_version = bytes.get(HEADER_LENGTH..payload_start).ok_or(ArtifactError::Truncated)?;
+
+    let payload = &bytes[payload_start..];
+    let found_checksum = checksum(payload);
+    if found_checksum != expected_checksum {
+        return Err(ArtifactError::ChecksumMismatch { found: found_checksum, expected: expected_checksum });
+    }
+    Ok((String::from_utf8_lossy(corpus_version).into_owned(), payload))
+}
+
+// Whether a collection's corpus version is one of the index's.
+pub fn check_collection(collection_corpus: &str, index_corpus: &str) -> Result<(), ArtifactError> {
+    if index_corpus.lines().any(|line| line == collection_corpus) {
+        Ok(())
+    } else {
+        Err(ArtifactError::CorpusMismatch {
+            collection: collection_corpus.to_string(),
+            index: index_corpus.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unpacks_what_it_packs() {
+        let packed = pack("bom 1 2019-11-08", b"and it came to pass");
+        assert_eq!(Ok((String::from("bom 1 2019-11-08"), &b"and it came to pass"[..])), unpack(&packed));
+        assert_eq!(Ok((String::new(), &b""[..])), unpack(&pack("", b"")));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let packed = pack("bom 1 2019-11-08", b"and it came to pass");
+
+        assert_eq!(Err(ArtifactError::NotAnArtifact), unpack(b"and it came to pass"));
+        assert_eq!(Err(ArtifactError::Truncated), unpack(&packed[..10]));
+        assert_eq!(Err(ArtifactError::Truncated), unpack(&packed[..HEADER_LENGTH + 3]));
+
+        let mut overlong = packed.clone();
+        overlong[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(Err(ArtifactError::Truncated), unpack(&overlong));
+
+        let mut newer = packed.clone();
+        newer[4] = SCHEMA_VERSION as u8 + 1;
+        assert_eq!(
+            Err(ArtifactError::SchemaMismatch { found: SCHEMA_VERSION + 1, expected: SCHEMA_VERSION }),
+            unpack(&newer),
+        );
+
+        let mut corrupt = packed.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        match unpack(&corrupt) {
+            Err(ArtifactError::ChecksumMismatch { .. }) => {},
+            other => panic!("expected a checksum mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn checks_collections_against_the_index() {
+        let index = "ot 1 2019-11-08\nnt 1 2019-11-08\nbom 2 2020-01-01";
+        assert_eq!(Ok(()), check_collection("bom 2 2020-01-01", index));
+        assert_eq!(
+            Err(ArtifactError::CorpusMismatch { collection: String::from("bom 1 2019-11-08"), index: index.to_string() }),
+            check_collection("bom 1 2019-11-08", index),
+        );
+    }
+}
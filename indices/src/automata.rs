@@ -0,0 +1,226 @@
+// Automata for searching the word FSTs without visiting every word. The FSTs
+// are walked a byte at a time, but both automata work on characters, so they
+// hold on to a character's bytes until the last of them arrives.
+
+use fst::Automaton;
+
+// Adds `byte` to the bytes of a partly read character, and returns the
+// character once it's complete.
+fn push_byte(pending: &mut Vec<u8>, byte: u8) -> Option<char> {
+    pending.push(byte);
+    match std::str::from_utf8(pending) {
+        Ok(complete) => {
+            let c = complete.chars().next();
+            pending.clear();
+            c
+        },
+        Err(_) => None,
+    }
+}
+
+fn fold(c: char, fold_case: bool) -> Vec<char> {
+    if fold_case {
+        c.to_lowercase().collect()
+    } else {
+        vec![c]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DistanceState {
+    // The last two rows of the edit distance table, with the character
+    // that produced the latest one.
+    previous: Vec<usize>,
+    row: Vec<usize>,
+    last: Option<char>,
+    pending: Vec<u8>,
+}
+
+// Accepts the words within `max_distance` optimal string alignment edits of
+// `word`: insertions, deletions, substitutions and swaps of neighboring
+// letters.
+pub struct EditDistance {
+    word: Vec<char>,
+    max_distance: usize,
+    fold_case: bool,
+}
+
+impl EditDistance {
+    pub fn new(word: &str, max_distance: usize, fold_case: bool) -> EditDistance {
+        EditDistance { word: word.chars().collect(), max_distance, fold_case }
+    }
+
+    fn advance(&self, state: &mut DistanceState, c: char) {
+        let mut row = vec![state.row[0] + 1; self.word.len() + 1];
+        for j in 1..=self.word.len() {
+            let cost = if self.word[j - 1] == c { 0 } else { 1 };
+            row[j] = std::cmp::min(std::cmp::min(state.row[j] + 1, row[j - 1] + 1), state.row[j - 1] + cost);
+            if j > 1 && state.last == Some(self.word[j - 1]) && self.word[j - 2] == c {
+                row[j] = std::cmp::min(row[j], state.previous[j - 2] + 1);
+            }
+        }
+        state.previous = std::mem::replace(&mut state.row, row);
+        state.last = Some(c);
+    }
+}
+
+impl Automaton for EditDistance {
+    type State = Option<DistanceState>;
+
+    fn start(&self) -> Self::State {
+        let row: Vec<usize> = (0..=self.word.len()).collect();
+        Some(DistanceState { previous: row.clone(), row, last: None, pending: vec![] })
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        match state {
+            Some(state) => state.pending.is_empty() && state.row[self.word.len()] <= self.max_distance,
+            None => false,
+        }
+    }
+
+    // A swap is never cheaper than the substitution it starts with, so once
+    // every cell of the latest row is over the limit, so is every later row.
+    fn can_match(&self, state: &Self::State) -> bool {
+        match state {
+            Some(state) => state.row.iter().any(|distance| *distance <= self.max_distance),
+            None => false,
+        }
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        let mut state = state.clone()?;
+        if let Some(c) = push_byte(&mut state.pending, byte) {
+            for c in fold(c, self.fold_case) {
+                self.advance(&mut state, c);
+            }
+        }
+        Some(state)
+    }
+}
+
+// Accepts the words matching a glob-style `pattern`, where `*` stands for
+// any run of characters, including none.
+pub struct Wildcard {
+    pattern: Vec<char>,
+    fold_case: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct WildcardState {
+    // Which positions in the pattern the word so far can have reached.
+    reached: Vec<bool>,
+    pending: Vec<u8>,
+}
+
+impl Wildcard {
+    pub fn new(pattern: &str, fold_case: bool) -> Wildcard {
+        let pattern = pattern.chars().flat_map(|c| fold(c, fold_case)).collect();
+        Wildcard { pattern, fold_case }
+    }
+
+    // A `*` may match nothing, so reaching one also reaches what follows it.
+    fn close(&self, reached: &mut [bool]) {
+        for (p, expected) in self.pattern.iter().enumerate() {
+            if reached[p] && *expected == '*' {
+                reached[p + 1] = true;
+            }
+        }
+    }
+
+    fn advance(&self, reached: &[bool], c: char) -> Vec<bool> {
+        let mut next = vec![false; reached.len()];
+        for (p, expected) in self.pattern.iter().enumerate() {
+            if !reached[p] {
+                continue;
+            }
+            if *expected == '*' {
+                next[p] = true;
+            } else if *expected == c {
+                next[p + 1] = true;
+            }
+        }
+        self.close(&mut next);
+        next
+    }
+}
+
+impl Automaton for Wildcard {
+    type State = Option<WildcardState>;
+
+    fn start(&self) -> Self::State {
+        let mut reached = vec![false; self.pattern.len() + 1];
+        reached[0] = true;
+        self.close(&mut reached);
+        Some(WildcardState { reached, pending: vec![] })
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        match state {
+            Some(state) => state.pending.is_empty() && state.reached[self.pattern.len()],
+            None => false,
+        }
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        match state {
+            Some(state) => state.reached.iter().any(|reached| *reached),
+            None => false,
+        }
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        let mut state = state.clone()?;
+        if let Some(c) = push_byte(&mut state.pending, byte) {
+            for c in fold(c, self.fold_case) {
+                state.reached = self.advance(&state.reached, c);
+            }
+        }
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepts<A: Automaton>(automaton: &A, word: &str) -> bool {
+        let mut state = automaton.start();
+        for byte in word.bytes() {
+            if !automaton.can_match(&state) {
+                return false;
+            }
+            state = automaton.accept(&state, byte);
+        }
+        automaton.is_match(&state)
+    }
+
+    #[test]
+    fn edit_distance_accepts_words_within_the_limit() {
+        let near_moroni = EditDistance::new("moroni", 1, false);
+        assert_eq!(true, accepts(&near_moroni, "moroni"));
+        assert_eq!(true, accepts(&near_moroni, "moronai"));
+        assert_eq!(true, accepts(&near_moroni, "mroni"));
+        assert_eq!(true, accepts(&near_moroni, "omroni"));
+        assert_eq!(false, accepts(&near_moroni, "mormon"));
+        assert_eq!(false, accepts(&near_moroni, "MOroni"));
+        assert_eq!(true, accepts(&EditDistance::new("moroni", 1, true), "MOroni"));
+        assert_eq!(true, accepts(&EditDistance::new("lamanite", 2, false), "lamanti"));
+    }
+
+    #[test]
+    fn edit_distance_counts_characters_rather_than_bytes() {
+        assert_eq!(true, accepts(&EditDistance::new("café", 1, false), "cafe"));
+        assert_eq!(true, accepts(&EditDistance::new("cafe", 1, false), "café"));
+    }
+
+    #[test]
+    fn wildcard_accepts_matching_words() {
+        assert_eq!(true, accepts(&Wildcard::new("bapti*", false), "baptize"));
+        assert_eq!(true, accepts(&Wildcard::new("*ness", false), "righteousness"));
+        assert_eq!(true, accepts(&Wildcard::new("M*r*n*", false), "Moroni"));
+        assert_eq!(false, accepts(&Wildcard::new("b*ism", false), "baptisms"));
+        assert_eq!(false, accepts(&Wildcard::new("nephi", false), "Nephi"));
+        assert_eq!(true, accepts(&Wildcard::new("nephi", true), "Nephi"));
+    }
+}
@@ -0,0 +1,57 @@
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+// The start of every gzip stream: its magic number, then deflate as the
+// compression method.
+const GZIP_HEADER: [u8; 3] = [0x1f, 0x8b, 0x08];
+
+pub fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    // Writing into a `Vec` can't fail.
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_HEADER)
+}
+
+// Gunzips artifacts that were compressed, and passes anything else through,
+// so that callers don't have to care which one they were served.
+pub fn decompress(bytes: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    if !is_compressed(&bytes) {
+        return Ok(bytes);
+    }
+    let mut decompressed = vec![];
+    GzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompresses_what_it_compresses() {
+        let bytes: Vec<u8> = (0..10_000).map(|i| (i % 7) as u8).collect();
+        let compressed = compress(&bytes);
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < bytes.len());
+        assert_eq!(bytes, decompress(compressed).unwrap());
+    }
+
+    #[test]
+    fn passes_uncompressed_bytes_through() {
+        assert_eq!(vec![1, 2, 3], decompress(vec![1, 2, 3]).unwrap());
+        assert_eq!(Vec::<u8>::new(), decompress(vec![]).unwrap());
+    }
+
+    #[test]
+    fn rejects_truncated_streams() {
+        let compressed = compress(b"and it came to pass");
+        assert!(decompress(compressed[..compressed.len() - 4].to_vec()).is_err());
+    }
+}
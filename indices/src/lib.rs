@@ -1,6 +1,447 @@
-extern crate phf;
-use primitive_types::U256;
-use scripture_types::{VersePath};
+extern crate bincode;
+extern crate fst;
+extern crate serde;
 
-include!("../../data-bundler/data/codegen-paths-index.rs");
-include!("../../data-bundler/data/codegen-words-index.rs");
+pub mod artifact;
+mod automata;
+pub mod compression;
+mod postings;
+mod words;
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::BuildHasher;
+
+use fnv::FnvHashMap;
+use fst::automaton::{AlwaysMatch, Automaton};
+use fst::{IntoStreamer, Streamer};
+use serde::{Deserialize, Serialize, Serializer};
+use scripture_types::{
+    FrontMatterPathsIndex, HeadingPathsIndex, PathsIndex, StemFormsIndex, SurfaceIndex, SynonymsIndex, WordsIndex,
+};
+
+pub use automata::{EditDistance, Wildcard};
+pub use postings::{decode_highlights, encode_highlights, PostingError};
+pub use words::get_word_ranges;
+use postings::{read_varint, write_varint};
+
+// The index is four sections, each prefixed with its length as a
+// little-endian u64:
+//
+// 1. an FST mapping each stem to where its postings start
+// 2. an FST mapping each surface form to where its postings start
+// 3. the postings: for each word, how many passages use it, then for each
+//    passage its scripture id (as the gap from the previous id), the length
+//    of its posting, and the posting itself
+// 4. `IndexMetadata`, serialized with bincode
+//
+// The FSTs and postings are read in place, so loading the index only has to
+// deserialize the metadata.
+
+// A hash map's entries in key order. Hash maps iterate in an order that
+// depends on how they were filled, so anything written out goes through this
+// to come out the same every time.
+pub fn sorted<K: Ord, V, H>(map: &HashMap<K, V, H>) -> BTreeMap<&K, &V> {
+    map.iter().collect()
+}
+
+// For `#[serde(serialize_with)]`: serializes a hash map in key order. It
+// still deserializes like any other map.
+pub fn serialize_sorted<K, V, H, S>(map: &HashMap<K, V, H>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    H: BuildHasher,
+    S: Serializer,
+{
+    serializer.collect_map(sorted(map))
+}
+
+// Everything besides the words that the client needs to search.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct IndexMetadata {
+    #[serde(serialize_with = "serialize_sorted")]
+    pub stem_forms: StemFormsIndex,
+    #[serde(serialize_with = "serialize_sorted")]
+    pub synonyms: SynonymsIndex,
+    #[serde(serialize_with = "serialize_sorted")]
+    pub paths: PathsIndex,
+    #[serde(serialize_with = "serialize_sorted")]
+    pub heading_paths: HeadingPathsIndex,
+    #[serde(serialize_with = "serialize_sorted")]
+    pub front_matter_paths: FrontMatterPathsIndex,
+    pub average_verse_length: f64,
+}
+
+#[derive(Debug)]
+pub enum IndexError {
+    Posting { word: String, scripture_id: u16, error: PostingError },
+    // A word's postings don't hold as many passages as they say, or run
+    // past the end of the index.
+    CorruptPostings { word: String, error: PostingError },
+    Fst(fst::Error),
+    Metadata(bincode::Error),
+    // The index stopped partway through a section or a posting.
+    Truncated,
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexError::Posting { word, scripture_id, error } => {
+                write!(f, "can't encode highlights of {:?} in scripture {}: {:?}", word, scripture_id, error)
+            },
+            IndexError::CorruptPostings { word, error } => write!(f, "corrupt postings for {:?}: {:?}", word, error),
+            IndexError::Fst(error) => write!(f, "malformed word list: {}", error),
+            IndexError::Metadata(error) => write!(f, "malformed index metadata: {}", error),
+            IndexError::Truncated => write!(f, "index is truncated"),
+        }
+    }
+}
+
+impl From<fst::Error> for IndexError {
+    fn from(error: fst::Error) -> Self {
+        IndexError::Fst(error)
+    }
+}
+
+impl From<bincode::Error> for IndexError {
+    fn from(error: bincode::Error) -> Self {
+        IndexError::Metadata(error)
+    }
+}
+
+// Appends every word's postings to `postings`, and returns an FST from each
+// word to where its postings start. FSTs have to be built in key order.
+fn write_words(words: &WordsIndex, postings: &mut Vec<u8>) -> Result<Vec<u8>, IndexError> {
+    let mut sorted_words: Vec<&String> = words.keys().collect();
+    sorted_words.sort();
+
+    let mut builder = fst::MapBuilder::memory();
+    for word in sorted_words {
+        builder.insert(word, postings.len() as u64)?;
+
+        let usages = &words[word];
+        let mut scripture_ids: Vec<&u16> = usages.keys().collect();
+        scripture_ids.sort();
+
+        write_varint(scripture_ids.len(), postings);
+        let mut previous = 0;
+        for scripture_id in scripture_ids {
+            let posting = encode_highlights(&usages[scripture_id]).map_err(|error| IndexError::Posting {
+                word: word.to_string(),
+                scripture_id: *scripture_id,
+                error,
+            })?;
+            write_varint((scripture_id - previous) as usize, postings);
+            write_varint(posting.len(), postings);
+            postings.extend(posting);
+            previous = *scripture_id;
+        }
+    }
+    Ok(builder.into_inner()?)
+}
+
+fn write_section(section: &[u8], out: &mut Vec<u8>) {
+    out.extend(&(section.len() as u64).to_le_bytes());
+    out.extend(section);
+}
+
+fn read_section<'a>(bytes: &'a [u8], position: &mut usize) -> Result<&'a [u8], IndexError> {
+    let mut length = [0; 8];
+    length.copy_from_slice(bytes.get(*position..*position + 8).ok_or(IndexError::Truncated)?);
+    *position += 8;
+
+    let length = u64::from_le_bytes(length) as usize;
+    let end = position.checked_add(length).ok_or(IndexError::Truncated)?;
+    let section = bytes.get(*position..end).ok_or(IndexError::Truncated)?;
+    *position = end;
+    Ok(section)
+}
+
+pub fn write_index(words: &WordsIndex, surface: &SurfaceIndex, metadata: &IndexMetadata) -> Result<Vec<u8>, IndexError> {
+    let mut postings = vec![];
+    let words_fst = write_words(words, &mut postings)?;
+    let surface_fst = write_words(surface, &mut postings)?;
+
+    let mut index = vec![];
+    write_section(&words_fst, &mut index);
+    write_section(&surface_fst, &mut index);
+    write_section(&postings, &mut index);
+    write_section(&bincode::serialize(metadata)?, &mut index);
+    Ok(index)
+}
+
+pub struct Index<'a> {
+    words: fst::Map<&'a [u8]>,
+    surface: fst::Map<&'a [u8]>,
+    postings: &'a [u8],
+    // The same postings as the FSTs point to, for looking up whole words.
+    stem_postings: FnvHashMap<String, Postings<'a>>,
+    surface_postings: FnvHashMap<String, Postings<'a>>,
+    pub metadata: IndexMetadata,
+}
+
+// Walks every word's postings, so that reading them later can't fail, and
+// returns them by word. Looking a word up by hash is quicker than walking the
+// FST to it. The highlights inside each posting are left to `decode_highlights`.
+fn read_postings<'a>(words: &fst::Map<&[u8]>, postings: &'a [u8]) -> Result<FnvHashMap<String, Postings<'a>>, IndexError> {
+    let mut by_word = FnvHashMap::default();
+    let mut stream = words.stream();
+    while let Some((word, offset)) = stream.next() {
+        let word = String::from_utf8_lossy(word).into_owned();
+        let corrupt = |error| IndexError::CorruptPostings { word: word.clone(), error };
+        let word_postings = Postings::at(postings, offset).map_err(corrupt)?;
+        let mut entries = word_postings.entries();
+        while entries.remaining > 0 {
+            entries.read_entry().map_err(corrupt)?;
+        }
+        by_word.insert(word, word_postings);
+    }
+    Ok(by_word)
+}
+
+impl<'a> Index<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Index<'a>, IndexError> {
+        let mut position = 0;
+        let words = fst::Map::new(read_section(bytes, &mut position)?)?;
+        let surface = fst::Map::new(read_section(bytes, &mut position)?)?;
+        let postings = read_section(bytes, &mut position)?;
+        let metadata = bincode::deserialize(read_section(bytes, &mut position)?)?;
+        let stem_postings = read_postings(&words, postings)?;
+        let surface_postings = read_postings(&surface, postings)?;
+        Ok(Index { words, surface, postings, stem_postings, surface_postings, metadata })
+    }
+
+    pub fn stem_count(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn surface_form_count(&self) -> usize {
+        self.surface.len()
+    }
+
+    // The passages using a stem.
+    pub fn word(&self, stem: &str) -> Option<Postings<'a>> {
+        self.stem_postings.get(stem).copied()
+    }
+
+    // The passages using a word exactly as written.
+    pub fn surface(&self, form: &str) -> Option<Postings<'a>> {
+        self.surface_postings.get(form).copied()
+    }
+
+    // Every stem, in byte order.
+    pub fn stems(&self) -> Terms<'_, 'a> {
+        self.search_stems(AlwaysMatch)
+    }
+
+    // Every surface form, in byte order.
+    pub fn surface_forms(&self) -> Terms<'_, 'a> {
+        self.search_surface_forms(AlwaysMatch)
+    }
+
+    // The stems `automaton` accepts, in byte order. Only the parts of the
+    // FST the automaton can still match in are visited.
+    pub fn search_stems<A: Automaton>(&self, automaton: A) -> Terms<'_, 'a, A> {
+        Terms { stream: self.words.search(automaton).into_stream(), postings: self.postings }
+    }
+
+    // The surface forms `automaton` accepts, in byte order.
+    pub fn search_surface_forms<A: Automaton>(&self, automaton: A) -> Terms<'_, 'a, A> {
+        Terms { stream: self.surface.search(automaton).into_stream(), postings: self.postings }
+    }
+}
+
+pub struct Terms<'s, 'a, A: Automaton = AlwaysMatch> {
+    stream: fst::map::Stream<'s, A>,
+    postings: &'a [u8],
+}
+
+impl<'s, 'a, A: Automaton> Iterator for Terms<'s, 'a, A> {
+    type Item = (String, Postings<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let postings = self.postings;
+        while let Some((word, offset)) = self.stream.next() {
+            if let Ok(postings) = Postings::at(postings, offset) {
+                return Some((String::from_utf8_lossy(word).into_owned(), postings));
+            }
+        }
+        None
+    }
+}
+
+// One word's postings, keyed by scripture id.
+#[derive(Clone, Copy, Debug)]
+pub struct Postings<'a> {
+    count: usize,
+    bytes: &'a [u8],
+}
+
+impl<'a> Postings<'a> {
+    fn at(postings: &'a [u8], offset: u64) -> Result<Postings<'a>, PostingError> {
+        let mut position = usize::try_from(offset).map_err(|_| PostingError::Overflow)?;
+        let count = read_varint(postings, &mut position)?;
+        let bytes = postings.get(position..).ok_or(PostingError::Truncated)?;
+        Ok(Postings { count, bytes })
+    }
+
+    // How many passages use the word.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Each passage's scripture id and posting, in scripture id order.
+    // Decode postings with `decode_highlights`.
+    pub fn entries(&self) -> PostingsEntries<'a> {
+        PostingsEntries { remaining: self.count, bytes: self.bytes, position: 0, previous: 0 }
+    }
+}
+
+pub struct PostingsEntries<'a> {
+    remaining: usize,
+    bytes: &'a [u8],
+    position: usize,
+    previous: u16,
+}
+
+impl<'a> PostingsEntries<'a> {
+    fn read_entry(&mut self) -> Result<(u16, &'a [u8]), PostingError> {
+        self.remaining -= 1;
+        let gap = read_varint(self.bytes, &mut self.position)?;
+        let length = read_varint(self.bytes, &mut self.position)?;
+        let end = self.position.checked_add(length).ok_or(PostingError::Overflow)?;
+        let posting = self.bytes.get(self.position..end).ok_or(PostingError::Truncated)?;
+        let scripture_id = u16::try_from(gap)
+            .ok()
+            .and_then(|gap| self.previous.checked_add(gap))
+            .ok_or(PostingError::Overflow)?;
+        self.position = end;
+        self.previous = scripture_id;
+        Ok((scripture_id, posting))
+    }
+}
+
+impl<'a> Iterator for PostingsEntries<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // `Index::from_bytes` has already read every entry once, so this
+        // only stops early on postings it didn't check.
+        match self.read_entry() {
+            Ok(entry) => Some(entry),
+            Err(_) => {
+                self.remaining = 0;
+                None
+            },
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scripture_types::VersePath;
+
+    fn insert(words: &mut WordsIndex, word: &str, scripture_id: u16, highlights: Vec<(usize, usize)>) {
+        words.entry(word.to_string()).or_default().insert(scripture_id, highlights);
+    }
+
+    fn highlights(postings: Postings) -> Vec<(u16, Vec<(usize, usize)>)> {
+        postings
+            .entries()
+            .map(|(scripture_id, posting)| (scripture_id, decode_highlights(posting).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let mut words: WordsIndex = FnvHashMap::default();
+        insert(&mut words, "faith", 300, vec![(10, 5)]);
+        insert(&mut words, "faith", 2, vec![(0, 5), (20, 5)]);
+        insert(&mut words, "hope", 7, vec![(3, 4)]);
+        let mut surface: SurfaceIndex = FnvHashMap::default();
+        insert(&mut surface, "Faith", 2, vec![(0, 5)]);
+        let mut metadata = IndexMetadata::default();
+        metadata.paths.insert(2, VersePath::PathDC(0, 1));
+        metadata.average_verse_length = 2.5;
+
+        let bytes = write_index(&words, &surface, &metadata).unwrap();
+        let index = Index::from_bytes(&bytes).unwrap();
+
+        assert_eq!(2, index.word("faith").unwrap().len());
+        assert_eq!(vec![(2, vec![(0, 5), (20, 5)]), (300, vec![(10, 5)])], highlights(index.word("faith").unwrap()));
+        assert_eq!(vec![(2, vec![(0, 5)])], highlights(index.surface("Faith").unwrap()));
+        assert!(index.word("Faith").is_none());
+        assert!(index.surface("faith").is_none());
+        assert_eq!(
+            vec![String::from("faith"), String::from("hope")],
+            index.stems().map(|(stem, _)| stem).collect::<Vec<String>>(),
+        );
+        assert_eq!(Some(&VersePath::PathDC(0, 1)), index.metadata.paths.get(&2));
+        assert_eq!(2.5, index.metadata.average_verse_length);
+    }
+
+    #[test]
+    fn writes_the_same_bytes_however_it_was_filled() {
+        let fill = |scripture_ids: Vec<u16>| {
+            let mut words: WordsIndex = FnvHashMap::default();
+            let mut metadata = IndexMetadata::default();
+            for scripture_id in scripture_ids {
+                insert(&mut words, &format!("word{}", scripture_id % 7), scripture_id, vec![(0, 5)]);
+                metadata.paths.insert(scripture_id, VersePath::PathDC(0, scripture_id));
+                metadata.stem_forms.insert(format!("word{}", scripture_id), vec![format!("Word{}", scripture_id)]);
+            }
+            write_index(&words, &FnvHashMap::default(), &metadata).unwrap()
+        };
+        assert_eq!(fill((1..1000).collect()), fill((1..1000).rev().collect()));
+    }
+
+    #[test]
+    fn rejects_truncated_indices() {
+        let bytes = write_index(&FnvHashMap::default(), &FnvHashMap::default(), &IndexMetadata::default()).unwrap();
+        match Index::from_bytes(&bytes[..bytes.len() - 1]) {
+            Err(IndexError::Truncated) => {},
+            _ => panic!("expected a truncated index"),
+        }
+    }
+
+    fn with_postings(postings: &[u8]) -> Result<(), IndexError> {
+        let mut words = fst::MapBuilder::memory();
+        words.insert("faith", 0).unwrap();
+        let mut bytes = vec![];
+        write_section(&words.into_inner().unwrap(), &mut bytes);
+        write_section(&fst::MapBuilder::memory().into_inner().unwrap(), &mut bytes);
+        write_section(postings, &mut bytes);
+        write_section(&bincode::serialize(&IndexMetadata::default()).unwrap(), &mut bytes);
+        Index::from_bytes(&bytes).map(|_| ())
+    }
+
+    #[test]
+    fn rejects_corrupt_postings() {
+        assert!(with_postings(&[2, 7, 1, 0, 3, 1, 0]).is_ok());
+        // Says it has two passages, but stops partway through the second.
+        match with_postings(&[2, 7, 1, 0, 3]) {
+            Err(IndexError::CorruptPostings { word, error: PostingError::Truncated }) => assert_eq!("faith", word),
+            _ => panic!("expected truncated postings"),
+        }
+        // The second passage's id is past u16::MAX.
+        match with_postings(&[2, 0xff, 0xff, 0x03, 0, 1, 0]) {
+            Err(IndexError::CorruptPostings { error: PostingError::Overflow, .. }) => {},
+            _ => panic!("expected a scripture id overflow"),
+        }
+    }
+}
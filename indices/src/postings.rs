@@ -0,0 +1,114 @@
+// A posting holds a verse's highlights as variable-length integers: for each
+// highlight, its distance from the previous highlight's start, then its
+// length. Every byte carries seven bits of a number, least significant
+// first, with the high bit set on all but a number's last byte.
+
+const USIZE_BITS: usize = std::mem::size_of::<usize>() * 8;
+
+#[derive(Debug, PartialEq)]
+pub enum PostingError {
+    // Highlights have to be sorted by where they start.
+    OutOfOrder { previous: usize, offset: usize },
+    // The posting stopped partway through a number or a highlight.
+    Truncated,
+    // A number was too big for a usize.
+    Overflow,
+}
+
+pub(crate) fn write_varint(mut value: usize, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub(crate) fn read_varint(bytes: &[u8], position: &mut usize) -> Result<usize, PostingError> {
+    let mut value: usize = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes.get(*position).ok_or(PostingError::Truncated)?;
+        *position += 1;
+
+        let bits = (byte & 0x7f) as usize;
+        if shift >= USIZE_BITS || (bits << shift) >> shift != bits {
+            return Err(PostingError::Overflow);
+        }
+        value |= bits << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+pub fn encode_highlights(highlights: &Vec<(usize, usize)>) -> Result<Vec<u8>, PostingError> {
+    let mut encoded = vec![];
+    let mut previous = 0;
+    for (offset, length) in highlights {
+        if *offset < previous {
+            return Err(PostingError::OutOfOrder { previous, offset: *offset });
+        }
+        write_varint(offset - previous, &mut encoded);
+        write_varint(*length, &mut encoded);
+        previous = *offset;
+    }
+    Ok(encoded)
+}
+
+pub fn decode_highlights(encoded: &[u8]) -> Result<Vec<(usize, usize)>, PostingError> {
+    let mut highlights = vec![];
+    let mut position = 0;
+    let mut previous: usize = 0;
+    while position < encoded.len() {
+        let gap = read_varint(encoded, &mut position)?;
+        let length = read_varint(encoded, &mut position)?;
+        let offset = previous.checked_add(gap).ok_or(PostingError::Overflow)?;
+        highlights.push((offset, length));
+        previous = offset;
+    }
+    Ok(highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_round_trip(highlights: Vec<(usize, usize)>) {
+        assert_eq!(Ok(highlights.clone()), decode_highlights(&encode_highlights(&highlights).unwrap()));
+    }
+
+    #[test]
+    fn encodes_and_decodes_highlights() {
+        test_round_trip(vec![]);
+        test_round_trip(vec![(0, 1), (4, 3), (9, 20)]);
+        test_round_trip((0..100).map(|i| (i * 6, 5)).collect());
+        test_round_trip(vec![(0, 5), (0, 7)]);
+    }
+
+    #[test]
+    fn encodes_highlights_past_the_old_packing_limits() {
+        // Offsets past 2047, lengths past 31, and more than 23 highlights.
+        test_round_trip(vec![(2048, 32), (70000, 300)]);
+        test_round_trip((0..50).map(|i| (i * 3000, 40)).collect());
+        test_round_trip(vec![(usize::MAX - 1, usize::MAX)]);
+    }
+
+    #[test]
+    fn rejects_unrepresentable_highlights() {
+        assert_eq!(
+            Err(PostingError::OutOfOrder { previous: 10, offset: 4 }),
+            encode_highlights(&vec![(10, 3), (4, 3)]),
+        );
+    }
+
+    #[test]
+    fn rejects_corrupt_postings() {
+        // Missing the last highlight's length.
+        assert_eq!(Err(PostingError::Truncated), decode_highlights(&[0, 5, 3]));
+        // Ends in the middle of a number.
+        assert_eq!(Err(PostingError::Truncated), decode_highlights(&[0x80]));
+        assert_eq!(Err(PostingError::Overflow), decode_highlights(&[0xff; 12]));
+    }
+}
@@ -0,0 +1,37 @@
+// Where each word in a passage starts and ends, as byte offsets. Highlights
+// are these ranges, so the bundler and the client have to agree on them.
+pub fn get_word_ranges(text: &str) -> Vec<(usize, usize)> {
+    let mut results: Vec<(usize, usize)> = vec![];
+    let mut open: Option<usize> = None;
+    text.char_indices().for_each(|(idx, letter): (usize, char)| {
+        let is_word_char = letter.is_alphanumeric() || letter == 'æ' || letter == '-';
+        if is_word_char {
+            if open.is_none() {
+                open = Some(idx);
+            }
+        } else {
+            if let Some(open_idx) = open {
+                results.push((open_idx, idx));
+                open = None;
+            }
+        }
+    });
+    // Headings and notes often end without punctuation.
+    if let Some(open_idx) = open {
+        results.push((open_idx, text.len()));
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_words_up_to_the_end_of_the_text() {
+        assert_eq!(vec![(0, 3), (4, 8), (9, 11), (12, 18)], get_word_ranges("The Book of Mormon"));
+        assert_eq!(vec![(0, 4), (5, 10)], get_word_ranges("Holy Ghost."));
+        assert_eq!(Vec::<(usize, usize)>::new(), get_word_ranges(""));
+    }
+}
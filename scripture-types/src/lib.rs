@@ -1,21 +1,23 @@
 extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
-extern crate phf;
-use primitive_types::U256;
 
 use serde::{Deserialize, Serialize};
 use fnv::FnvHashMap;
 
 pub type WordsIndex = FnvHashMap<String, FnvHashMap<u16, Vec<(usize, usize)>>>;
+// Same shape as `WordsIndex`, but keyed by unstemmed (surface) word forms.
+pub type SurfaceIndex = WordsIndex;
+pub type StemFormsIndex = FnvHashMap<String, Vec<String>>;
+pub type SynonymsIndex = FnvHashMap<String, Vec<String>>;
 pub type PathsIndex = FnvHashMap<u16, VersePath>;
+pub type HeadingPathsIndex = FnvHashMap<u16, HeadingPath>;
+pub type FrontMatterPathsIndex = FnvHashMap<u16, FrontMatterPath>;
 pub type VersePathsIndex = FnvHashMap<VersePath, u16>;
-pub type PhfPathsIndex = phf::Map<u16, VersePath>;
-pub type PhfWordsIndex = phf::Map<&'static str, phf::Map<u16, (U256, u128)>>;
 
-pub fn paths_to_verse_paths_index(paths: &PhfPathsIndex) -> VersePathsIndex {
+pub fn paths_to_verse_paths_index(paths: &PathsIndex) -> VersePathsIndex {
     paths
-        .entries()
+        .iter()
         .fold(
             FnvHashMap::default(),
             |mut acc, (k, v)| {
@@ -34,6 +36,36 @@ pub enum VersePath {
     PathDC(u8, u16), // section verse
 }
 
+// Text that introduces a verse, chapter or book rather than being part of it.
+// Listed in reading order, which search results are sorted by.
+#[derive(Serialize, Deserialize, Debug, Hash, Eq, PartialEq, Clone, Copy)]
+pub enum HeadingField {
+    BookHeading,
+    BookNote,
+    ChapterHeading,
+    ChapterNote,
+    VerseHeading,
+    VerseSubheading,
+}
+
+// Where a heading or note lives. Chapter fields point at the chapter's first
+// verse, and book fields at the book's first verse.
+#[derive(Serialize, Deserialize, Debug, Hash, Eq, PartialEq, Clone)]
+pub struct HeadingPath {
+    pub verse_path: VersePath,
+    pub field: HeadingField,
+}
+
+// Passages that sit outside of any chapter or section's verses.
+#[derive(Serialize, Deserialize, Debug, Hash, Eq, PartialEq, Clone)]
+pub enum FrontMatterPath {
+    BoMTitlePage(u8), // paragraph
+    BoMTestimony(u8), // testimony
+    NTTitlePage,
+    FacsimileExplanation(u8, u8, u8), // book facsimile explanation
+    DCSignature(u8), // section
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Verse {
     pub heading: Option<String>,
@@ -55,12 +87,12 @@ pub struct Chapter {
 
 #[derive(Serialize, Deserialize)]
 pub struct Facsimile {
-    explanations: Vec<String>,
-    image_url: String,
-    lds_slug: String,
-    note: Option<String>,
-    number: u64,
-    title: String,
+    pub explanations: Vec<String>,
+    pub image_url: String,
+    pub lds_slug: String,
+    pub note: Option<String>,
+    pub number: u64,
+    pub title: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -106,9 +138,9 @@ pub struct TitlePage {
 
 #[derive(Serialize, Deserialize)]
 pub struct Testimony {
-    text: String,
-    title: String,
-    witnesses: Vec<String>,
+    pub text: String,
+    pub title: String,
+    pub witnesses: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -124,9 +156,9 @@ pub struct DoctrineAndCovenants {
 
 #[derive(Serialize, Deserialize)]
 pub struct NewTestamentTitlePage {
-    subtitle: String,
-    text: String,
-    title: String,
+    pub subtitle: String,
+    pub text: String,
+    pub title: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -158,3 +190,27 @@ pub struct PearlOfGreatPrice {
     pub title: String,
     pub version: u8,
 }
+
+// Which release of the scriptures-json data a collection came from.
+pub trait Versioned {
+    fn version(&self) -> u8;
+    fn last_modified(&self) -> &str;
+}
+
+macro_rules! impl_versioned {
+    ($($collection:ty),*) => {
+        $(
+            impl Versioned for $collection {
+                fn version(&self) -> u8 {
+                    self.version
+                }
+
+                fn last_modified(&self) -> &str {
+                    &self.last_modified
+                }
+            }
+        )*
+    };
+}
+
+impl_versioned!(OldTestament, NewTestament, BookOfMormon, DoctrineAndCovenants, PearlOfGreatPrice);